@@ -0,0 +1,23 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::get_data_path;
+
+const AUDIT_LOG: &str = "ivan_audit.log";
+
+/// Appends a line to the audit trail next to the config file. Failing to write the audit trail
+/// is only printed, it never stops the command that is being audited.
+pub fn audit(author: u64, action: String) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|value| value.as_secs()).unwrap_or(0);
+    let line = format!("[{}] <{}> {}", timestamp, author, action);
+    println!("audit: {}", line);
+    let file = OpenOptions::new().create(true).append(true).open(get_data_path(AUDIT_LOG));
+    match file {
+        Ok(mut file) => {
+            writeln!(file, "{}", line).unwrap_or_else(|err| {
+                println!("could not write audit log because of {}", err);
+            });
+        }
+        Err(err) => println!("could not open audit log because of {}", err)
+    }
+}
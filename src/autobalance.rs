@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::audit::audit;
+use crate::background::{announce, PlayerSnapshot};
+use crate::config::{IvanConfig, Player, PlayerInfo};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, invalid_argument, get_server_info};
+use crate::pavlov::{PavlovCommands, TeamId, pa, parse_number};
+use crate::rules::{imbalance, team_of};
+use crate::help::{HELP_AUTOBALANCE, HELP_AUTOBALANCE_VALUE};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AutoBalance {
+    pub enabled: bool,
+    /// Balance once one team has more than this many players more than the other.
+    pub threshold: u64,
+    /// Minutes between two balances.
+    pub cooldown: u64,
+}
+
+impl Default for AutoBalance {
+    fn default() -> Self {
+        AutoBalance { enabled: false, threshold: 2, cooldown: 5 }
+    }
+}
+
+/// What the background thread remembers between balance checks.
+#[derive(Default)]
+pub struct AutoBalanceState {
+    /// When each player on the server was first seen, the latest players are moved. Kept up to date
+    /// while autobalance is off too, only the players that were there when the bot started share a time.
+    joined: HashMap<u64, Instant>,
+    balanced: Option<Instant>,
+}
+
+/// `-team autobalance [on, off]`, `-team autobalance threshold players` and `-team autobalance cooldown minutes`.
+pub fn handle_autobalance(arguments: &Vec<&str>, config: &mut IvanConfig, author: u64) -> Result<String, IvanError> {
+    let mut settings = config.get_autobalance().clone();
+    let response = match pa(arguments, 2, HELP_AUTOBALANCE)?.to_lowercase().as_str() {
+        "on" => {
+            settings.enabled = true;
+            format!("Teams are balanced when they differ by more than {} players", settings.threshold)
+        }
+        "off" => {
+            settings.enabled = false;
+            "Teams are not balanced automatically anymore".to_string()
+        }
+        "threshold" => {
+            settings.threshold = parse_number(pa(arguments, 3, HELP_AUTOBALANCE_VALUE)?)?;
+            format!("Teams are balanced when they differ by more than {} players", settings.threshold)
+        }
+        "cooldown" => {
+            settings.cooldown = parse_number(pa(arguments, 3, HELP_AUTOBALANCE_VALUE)?)?;
+            format!("Teams are balanced at most once every {} minutes", settings.cooldown)
+        }
+        x => return invalid_argument(x, HELP_AUTOBALANCE)
+    };
+    config.set_autobalance(settings)?;
+    audit(author, response.clone());
+    Ok(response)
+}
+
+/// Remembers when the players on the server joined.
+pub fn track_joins(state: &mut AutoBalanceState, players: &[Player]) {
+    state.joined.retain(|steam_id, _| players.iter().any(|player| player.UniqueId == steam_id.to_string()));
+    for player in players {
+        if let Ok(steam_id) = player.UniqueId.parse() {
+            state.joined.entry(steam_id).or_insert_with(Instant::now);
+        }
+    }
+}
+
+/// Moves the most recently joined players of the bigger team when the teams are too far apart.
+pub fn run_autobalance(framework: &mut CustomFramework, http: &Http, snapshot: &mut PlayerSnapshot, state: &mut AutoBalanceState) {
+    let settings = framework.config.get_autobalance().clone();
+    if state.balanced.map(|balanced| balanced.elapsed() < Duration::from_secs(settings.cooldown * 60)).unwrap_or(false) {
+        return;
+    }
+    let players = match snapshot.inspected(framework) {
+        Ok(players) => players,
+        Err(err) => {
+            println!("could not check the team balance: {}", err);
+            return;
+        }
+    };
+    let difference = imbalance(&players);
+    if difference <= settings.threshold {
+        return;
+    }
+    // moving players in the middle of a search and destroy round kills them
+    match get_server_info(framework) {
+        Ok(info) if info.GameMode.eq_ignore_ascii_case("SND") && info.RoundState.eq_ignore_ascii_case("Started") => return,
+        Ok(_) => {}
+        Err(err) => {
+            println!("could not check the team balance: {}", err);
+            return;
+        }
+    }
+    let red = players.iter().filter(|player| team_of(player) == 1).count();
+    let blue = players.len() - red;
+    let (bigger, smaller): (TeamId, TeamId) = if red > blue { (1, 0) } else { (0, 1) };
+    let moved = latest_joined(&players, &state.joined, bigger, (difference / 2) as usize);
+    for (_, steam_id) in &moved {
+        framework.connection.execute_command(PavlovCommands::SwitchTeam(*steam_id, smaller));
+    }
+    state.balanced = Some(Instant::now());
+    let names = moved.iter().map(|(player, _)| player.PlayerName.clone()).collect::<Vec<String>>().join(", ");
+    audit(0, format!("autobalance moved {} to team {}", names, smaller));
+    announce(http, &framework.config, format!("The teams were {} against {}, moved {} to the other team", std::cmp::max(red, blue), std::cmp::min(red, blue), names));
+}
+
+/// The `count` players of `team` that joined last, with their steam ids.
+fn latest_joined<'a>(players: &'a [PlayerInfo], joined: &HashMap<u64, Instant>, team: TeamId, count: usize) -> Vec<(&'a PlayerInfo, u64)> {
+    let mut candidates: Vec<(&PlayerInfo, u64, Instant)> = players.iter().filter(|player| team_of(player) == team).filter_map(|player| {
+        let steam_id: u64 = player.UniqueId.parse().ok()?;
+        joined.get(&steam_id).map(|joined| (player, steam_id, *joined))
+    }).collect();
+    candidates.sort_by_key(|(_, _, joined)| std::cmp::Reverse(*joined));
+    candidates.into_iter().take(count).map(|(player, steam_id, _)| (player, steam_id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(steam_id: &str, team: &str) -> PlayerInfo {
+        PlayerInfo { UniqueId: steam_id.to_string(), ..crate::rules::tests::player(steam_id, "0/0/0", "0", team) }
+    }
+
+    #[test]
+    fn joins_are_kept_while_players_stay() {
+        let mut state = AutoBalanceState::default();
+        let list = |ids: &[&str]| ids.iter().map(|id| Player { Username: id.to_string(), UniqueId: id.to_string() }).collect::<Vec<Player>>();
+        track_joins(&mut state, &list(&["1", "2"]));
+        let first = state.joined[&1];
+        track_joins(&mut state, &list(&["1", "3"]));
+        assert_eq!(state.joined[&1], first);
+        assert!(state.joined.contains_key(&3));
+        assert!(!state.joined.contains_key(&2));
+    }
+
+    #[test]
+    fn the_latest_players_of_the_team_are_picked() {
+        let start = Instant::now();
+        let joined: HashMap<u64, Instant> = vec![(1, start), (2, start + Duration::from_secs(10)), (3, start + Duration::from_secs(20)), (4, start + Duration::from_secs(30))].into_iter().collect();
+        let players = vec![player("1", "1"), player("2", "1"), player("3", "1"), player("4", "0")];
+        let picked: Vec<u64> = latest_joined(&players, &joined, 1, 2).into_iter().map(|(_, steam_id)| steam_id).collect();
+        assert_eq!(picked, vec![3, 2]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use crate::config::{serialize_config, IvanConfig, Player, PlayerInfo};
+use crate::storage::get_storage;
+use crate::pools::{current_minute, due_pool};
+use crate::votekick::expire_temp_bans;
+use crate::whitelist::enforce_whitelist;
+use crate::rules::{RulesState, run_rules};
+use crate::autobalance::{AutoBalanceState, track_joins, run_autobalance};
+use crate::matches::{MatchEvent, MatchTracker, track_match};
+use crate::economy::{EconomyState, economy_map_changed, economy_round_started};
+use crate::mapactions::{MapActionState, map_actions_map_changed, run_map_actions};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, get_player_list, inspect_all};
+
+const TICK: Duration = Duration::from_secs(5);
+/// How often the player list is checked against the whitelist.
+const WHITELIST_INTERVAL: Duration = Duration::from_secs(15);
+/// How often the moderation rules are checked, every check inspects all players.
+const RULES_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the team sizes are checked, join times are tracked while autobalance is off too.
+const BALANCE_INTERVAL: Duration = Duration::from_secs(20);
+/// How often ServerInfo is polled to follow rounds and matches.
+const MATCH_INTERVAL: Duration = Duration::from_secs(15);
+/// How long inspected players are reused, every task that needs them shares one inspection.
+const SNAPSHOT_AGE: Duration = Duration::from_secs(15);
+
+/// The player list of the current tick and the last inspection of those players. The background
+/// thread holds the framework while it polls, so every RCON call here blocks the commands.
+#[derive(Default)]
+pub struct PlayerSnapshot {
+    players: Option<Vec<Player>>,
+    inspected: Option<(Instant, Vec<PlayerInfo>)>,
+}
+
+impl PlayerSnapshot {
+    /// The players on the server, asked once per tick.
+    pub fn players(&mut self, framework: &mut CustomFramework) -> Result<Vec<Player>, IvanError> {
+        if let Some(players) = &self.players {
+            return Ok(players.clone());
+        }
+        let players = get_player_list(framework)?;
+        self.players = Some(players.clone());
+        Ok(players)
+    }
+
+    /// The inspected players, inspected again once the last inspection is `SNAPSHOT_AGE` old.
+    pub fn inspected(&mut self, framework: &mut CustomFramework) -> Result<Vec<PlayerInfo>, IvanError> {
+        if let Some((inspected, players)) = &self.inspected {
+            if inspected.elapsed() < SNAPSHOT_AGE {
+                return Ok(players.clone());
+            }
+        }
+        let players = inspect_all(self.players(framework)?, framework)?;
+        self.inspected = Some((Instant::now(), players.clone()));
+        Ok(players)
+    }
+}
+
+/// State the background thread keeps between ticks.
+struct BackgroundState {
+    config_modified: Option<SystemTime>,
+    schedule_minute: u64,
+    whitelist_checked: Instant,
+    /// When players that are not on the whitelist were first seen on the server.
+    whitelist_seen: HashMap<u64, Instant>,
+    rules_checked: Instant,
+    rules: RulesState,
+    balance_checked: Instant,
+    balance: AutoBalanceState,
+    match_checked: Instant,
+    tracker: MatchTracker,
+    economy: EconomyState,
+    map_actions: MapActionState,
+    snapshot: PlayerSnapshot,
+}
+
+/// Starts the thread that runs the periodic tasks of the bot.
+pub fn start_background(framework: Arc<Mutex<CustomFramework>>, http: Arc<Http>) {
+    std::thread::spawn(move || {
+        let mut state = BackgroundState { config_modified: get_storage().modified(), schedule_minute: current_minute(), whitelist_checked: Instant::now(), whitelist_seen: HashMap::new(), rules_checked: Instant::now(), rules: RulesState::default(), balance_checked: Instant::now(), balance: AutoBalanceState::default(), match_checked: Instant::now(), tracker: MatchTracker::default(), economy: EconomyState::default(), map_actions: MapActionState::default(), snapshot: PlayerSnapshot::default() };
+        loop {
+            sleep(TICK);
+            match framework.lock() {
+                Ok(mut framework) => {
+                    state.snapshot.players = None;
+                    watch_config(&mut framework, &http, &mut state);
+                    run_schedules(&mut framework, &http, &mut state);
+                    expire_temp_bans(&mut framework, &http);
+                    check_whitelist(&mut framework, &http, &mut state);
+                    check_rules(&mut framework, &http, &mut state);
+                    check_balance(&mut framework, &http, &mut state);
+                    check_match(&mut framework, &http, &mut state);
+                    run_map_actions(&mut framework, &http, &mut state.map_actions, state.tracker.last_info(), &mut state.snapshot);
+                }
+                Err(err) => {
+                    println!("background tasks stopped, mutex error {}", err);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Sends a message to the log channel, or prints it when no channel is configured.
+pub fn announce(http: &Http, config: &IvanConfig, message: String) {
+    println!("{}", message);
+    if let Some(channel) = config.get_log_channel() {
+        ChannelId(channel).say(http, &message).map(|_| ()).unwrap_or_else(|err| {
+            println!("could not announce \"{}\" because of {}", message, err);
+        });
+    }
+}
+
+/// Re-reads the stored config and swaps it in when it is valid, the running config is kept otherwise.
+pub fn reload_config(framework: &mut CustomFramework) -> Result<(), IvanError> {
+    framework.config = get_storage().load()?;
+    Ok(())
+}
+
+/// Reloads the config when the storage was edited outside the bot.
+fn watch_config(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    let storage = get_storage();
+    let modified = storage.modified();
+    if modified == state.config_modified {
+        return;
+    }
+    state.config_modified = modified;
+    match storage.load() {
+        // the bot writes the config itself after every change, those writes are not edits
+        Ok(config) if serialize_config(&config).ok() == serialize_config(&framework.config).ok() => {}
+        Ok(config) => {
+            framework.config = config;
+            announce(http, &framework.config, "The config was changed on disk, reloaded it".to_string());
+        }
+        Err(err) => announce(http, &framework.config, format!("The config was changed on disk but could not be loaded, keeping the running config: {}", err))
+    }
+}
+
+/// Switches the active pool when a pool schedule is due, once per minute.
+fn run_schedules(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    let minute = current_minute();
+    if minute <= state.schedule_minute {
+        return;
+    }
+    let pool = due_pool(framework.config.get_schedules(), state.schedule_minute, minute);
+    state.schedule_minute = minute;
+    let pool = match pool {
+        Some(pool) => pool,
+        None => return
+    };
+    if framework.config.get_active_pool().map(|active| active.eq_ignore_ascii_case(&pool)).unwrap_or(false) {
+        return;
+    }
+    match framework.config.use_pool(Some(pool.clone())) {
+        Ok(_) => announce(http, &framework.config, format!("Scheduled switch, votes now use pool \"{}\"", pool)),
+        Err(err) => announce(http, &framework.config, format!("Could not switch to the scheduled pool \"{}\": {}", pool, err))
+    }
+}
+
+/// Polls the player list for the whitelist, skipped while the whitelist is off.
+fn check_whitelist(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    if !framework.config.get_whitelist().enabled {
+        state.whitelist_seen.clear();
+        return;
+    }
+    if state.whitelist_checked.elapsed() < WHITELIST_INTERVAL {
+        return;
+    }
+    state.whitelist_checked = Instant::now();
+    match state.snapshot.players(framework) {
+        Ok(players) => enforce_whitelist(framework, http, &players, &mut state.whitelist_seen),
+        Err(err) => println!("could not check the whitelist: {}", err)
+    }
+}
+
+/// Runs the moderation rules, skipped while there are none or nobody is online.
+fn check_rules(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    if framework.config.get_rules().rules.is_empty() || state.rules_checked.elapsed() < RULES_INTERVAL {
+        return;
+    }
+    state.rules_checked = Instant::now();
+    if !is_online(framework, &mut state.snapshot) {
+        return;
+    }
+    match state.snapshot.inspected(framework) {
+        Ok(players) => run_rules(framework, http, &players, &mut state.rules),
+        Err(err) => println!("could not check the rules: {}", err)
+    }
+}
+
+/// Tracks when players joined and balances the teams when autobalance is on.
+fn check_balance(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    if state.balance_checked.elapsed() < BALANCE_INTERVAL {
+        return;
+    }
+    state.balance_checked = Instant::now();
+    let players = match state.snapshot.players(framework) {
+        Ok(players) => players,
+        Err(err) => {
+            println!("could not check the team balance: {}", err);
+            return;
+        }
+    };
+    track_joins(&mut state.balance, &players);
+    if framework.config.get_autobalance().enabled {
+        run_autobalance(framework, http, &mut state.snapshot, &mut state.balance);
+    }
+}
+
+/// Follows the match on the server and reacts to what changed, paused while nobody is online.
+fn check_match(framework: &mut CustomFramework, http: &Http, state: &mut BackgroundState) {
+    if state.match_checked.elapsed() < MATCH_INTERVAL {
+        return;
+    }
+    state.match_checked = Instant::now();
+    if !is_online(framework, &mut state.snapshot) {
+        return;
+    }
+    for event in track_match(framework, http, &mut state.tracker, &mut state.snapshot) {
+        match event {
+            MatchEvent::MapChanged(map) => {
+                let name = framework.config.find_pool_map(&map).map(|pool_map| pool_map.alias.clone()).unwrap_or(map);
+                announce(http, &framework.config, format!("The map changed to {}", name));
+                economy_map_changed(&mut state.economy);
+                if let Some(info) = state.tracker.last_info().cloned() {
+                    map_actions_map_changed(&mut state.map_actions, info);
+                }
+            }
+            MatchEvent::RoundStarted => {
+                if let Some(info) = state.tracker.last_info().cloned() {
+                    economy_round_started(framework, http, &mut state.economy, &info);
+                }
+            }
+            MatchEvent::RoundEnded | MatchEvent::MatchEnded => {}
+        }
+    }
+}
+
+fn is_online(framework: &mut CustomFramework, snapshot: &mut PlayerSnapshot) -> bool {
+    match snapshot.players(framework) {
+        Ok(players) => !players.is_empty(),
+        Err(err) => {
+            println!("could not get the player list: {}", err);
+            false
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::get_data_path;
+use crate::model::{IvanError, BotErrorKind};
+
+const BACKUP_DIRECTORY: &str = "ivan_backups";
+const MAX_BACKUPS: usize = 10;
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: u64,
+}
+
+/// Keeps `previous`, the config as it was stored before a write, in the backup directory and
+/// removes the oldest backups so that at most `MAX_BACKUPS` are kept. Nothing is written when the
+/// newest backup already holds the same config.
+pub fn backup_config(previous: &str) -> Result<(), IvanError> {
+    if let Some(newest) = list_backups()?.first() {
+        if fs::read_to_string(&newest.path).map(|contents| contents == previous).unwrap_or(false) {
+            return Ok(());
+        }
+    }
+    let directory = get_data_path(BACKUP_DIRECTORY);
+    fs::create_dir_all(&directory).map_err(|err| backup_error(&directory, err))?;
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|value| value.as_millis()).unwrap_or(0);
+    let backup = Path::new(&directory).join(format!("ivan-{:015}.json", millis));
+    write_atomic(&backup.to_string_lossy(), previous).map_err(|err| {
+        IvanError { input: err.input, kind: BotErrorKind::BackupError }
+    })?;
+    for old in list_backups()?.iter().skip(MAX_BACKUPS) {
+        fs::remove_file(&old.path).unwrap_or_else(|err| {
+            println!("could not remove old backup {} because of {}", old.path.to_string_lossy(), err);
+        });
+    }
+    Ok(())
+}
+
+/// All backups, newest first.
+pub fn list_backups() -> Result<Vec<Backup>, IvanError> {
+    let directory = get_data_path(BACKUP_DIRECTORY);
+    if !Path::new(&directory).exists() {
+        return Ok(vec![]);
+    }
+    let entries = fs::read_dir(&directory).map_err(|err| backup_error(&directory, err))?;
+    let mut backups: Vec<Backup> = entries.filter_map(|entry| entry.ok()).filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let millis: u64 = name.strip_prefix("ivan-")?.strip_suffix(".json")?.parse().ok()?;
+        Some(Backup { path: entry.path(), created: millis / 1000 })
+    }).collect();
+    backups.sort_by(|first, second| second.path.cmp(&first.path));
+    Ok(backups)
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`, so a crash
+/// halfway leaves either the old or the new file but never a partial one.
+pub fn write_atomic(path: &str, contents: &str) -> Result<(), IvanError> {
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, contents).map_err(|err| write_error(&temporary, err))?;
+    fs::File::open(&temporary).and_then(|file| file.sync_all()).map_err(|err| write_error(&temporary, err))?;
+    fs::rename(&temporary, path).map_err(|err| write_error(path, err))
+}
+
+pub fn format_age(created: u64) -> String {
+    let age = now().saturating_sub(created);
+    match age {
+        0..=119 => format!("{} seconds ago", age),
+        120..=7199 => format!("{} minutes ago", age / 60),
+        7200..=172_799 => format!("{} hours ago", age / 3600),
+        _ => format!("{} days ago", age / 86400)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|value| value.as_secs()).unwrap_or(0)
+}
+
+fn backup_error(path: &str, err: std::io::Error) -> IvanError {
+    IvanError { input: format!("{}: {}", path, err), kind: BotErrorKind::BackupError }
+}
+
+fn write_error(path: &str, err: std::io::Error) -> IvanError {
+    IvanError { input: format!("{}: {}", path, err), kind: BotErrorKind::WriteError }
+}
@@ -0,0 +1,201 @@
+use rust_embed::RustEmbed;
+use serde::{Deserialize, Serialize};
+use serde_json::from_slice;
+use crate::config::IvanConfig;
+use crate::model::{IvanError, invalid_argument};
+use crate::pavlov::{pa, GameMode};
+use crate::help::{HELP_CATALOG_ARGUMENT, HELP_CATALOG_ENTRY, HELP_CATALOG_MODE_NAME, HELP_CATALOG_MAP_NAME};
+
+const CATALOG_FILE: &str = "catalog.json";
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Assets;
+
+/// Known game modes and stock maps. The bundled catalog ships with the bot, admins extend it
+/// at runtime and those extensions are stored in the config.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Catalog {
+    #[serde(default)]
+    pub modes: Vec<ModeEntry>,
+    #[serde(default)]
+    pub maps: Vec<String>,
+    #[serde(default)]
+    pub item_categories: Vec<ItemCategory>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModeEntry {
+    pub name: String,
+    #[serde(default)]
+    pub teams: bool,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ItemCategory {
+    pub name: String,
+    pub items: Vec<String>,
+}
+
+impl Catalog {
+    pub fn bundled() -> Catalog {
+        let file = Assets::get(CATALOG_FILE).expect("the bundled catalog.json is missing");
+        from_slice(file.as_ref()).expect("the bundled catalog.json is invalid")
+    }
+
+    /// Returns a catalog with the entries of `extension` added to this one, entries that already
+    /// exist are replaced by the ones from the extension.
+    pub fn merge(&self, extension: &Catalog) -> Catalog {
+        let mut modes: Vec<ModeEntry> = self.modes.iter().filter(|mode| {
+            !extension.modes.iter().any(|value| value.name.eq_ignore_ascii_case(&mode.name))
+        }).cloned().collect();
+        modes.extend(extension.modes.iter().cloned());
+        let mut maps: Vec<String> = self.maps.clone();
+        maps.extend(extension.maps.iter().filter(|map| {
+            !self.maps.iter().any(|value| value.eq_ignore_ascii_case(map))
+        }).cloned());
+        let mut item_categories: Vec<ItemCategory> = self.item_categories.iter().filter(|category| {
+            !extension.item_categories.iter().any(|value| value.name.eq_ignore_ascii_case(&category.name))
+        }).cloned().collect();
+        item_categories.extend(extension.item_categories.iter().cloned());
+        Catalog { modes, maps, item_categories }
+    }
+
+    pub fn find_mode(&self, name: &str) -> Option<&ModeEntry> {
+        self.modes.iter().find(|mode| mode.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_mode(&self, mode: &GameMode) -> bool {
+        self.find_mode(mode.name()).is_some()
+    }
+
+    pub fn is_team_mode(&self, mode: &GameMode) -> bool {
+        self.find_mode(mode.name()).map(|entry| entry.teams).unwrap_or(false)
+    }
+
+    pub fn is_map(&self, map: &str) -> bool {
+        self.maps.iter().any(|value| value.eq_ignore_ascii_case(map))
+    }
+
+    pub fn find_item_category(&self, name: &str) -> Option<&ItemCategory> {
+        self.item_categories.iter().find(|category| category.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn help_game_modes(&self) -> String {
+        let names: Vec<&str> = self.modes.iter().map(|mode| mode.name.as_str()).collect();
+        format!("valid game modes are [{}]", names.join(", "))
+    }
+
+    fn add_mode(&mut self, entry: ModeEntry) {
+        self.modes.retain(|mode| !mode.name.eq_ignore_ascii_case(&entry.name));
+        self.modes.push(entry);
+    }
+
+    fn remove_mode(&mut self, name: &str) {
+        self.modes.retain(|mode| !mode.name.eq_ignore_ascii_case(name));
+    }
+
+    fn add_map(&mut self, map: String) {
+        self.maps.retain(|value| !value.eq_ignore_ascii_case(&map));
+        self.maps.push(map);
+    }
+
+    fn remove_map(&mut self, map: &str) {
+        self.maps.retain(|value| !value.eq_ignore_ascii_case(map));
+    }
+}
+
+pub fn handle_catalog(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_CATALOG_ARGUMENT)?.to_lowercase().as_str() {
+        "modes" => {
+            let catalog = config.get_catalog();
+            Ok(catalog.modes.iter().fold("".to_string(), |a, mode| {
+                format!("{}\n{}{} {}", a, mode.name, if mode.teams { " (teams)" } else { "" }, mode.description)
+            }))
+        }
+        "maps" => Ok(config.get_catalog().maps.iter().fold("".to_string(), |a, b| format!("{}\n{}", a, b))),
+        "mode" => handle_catalog_mode(arguments, config),
+        "map" => handle_catalog_map(arguments, config),
+        x => invalid_argument(x, HELP_CATALOG_ARGUMENT)
+    }
+}
+
+fn handle_catalog_mode(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    let action = pa(arguments, 2, HELP_CATALOG_ENTRY)?;
+    let name = pa(arguments, 3, HELP_CATALOG_MODE_NAME)?.to_uppercase();
+    match action.to_lowercase().as_str() {
+        "add" => {
+            let teams = arguments.get(4).map(|value| value.eq_ignore_ascii_case("teams")).unwrap_or(false);
+            let description = arguments.iter().skip(if teams { 5 } else { 4 }).cloned().collect::<Vec<&str>>().join(" ");
+            config.update_catalog(|catalog| catalog.add_mode(ModeEntry { name: name.clone(), teams, description }))?;
+            Ok(format!("Added game mode \"{}\" to the catalog", name))
+        }
+        "remove" => {
+            config.update_catalog(|catalog| catalog.remove_mode(&name))?;
+            Ok(format!("Removed game mode \"{}\" from the catalog (bundled modes can't be removed)", name))
+        }
+        x => invalid_argument(x, HELP_CATALOG_ENTRY)
+    }
+}
+
+fn handle_catalog_map(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    let action = pa(arguments, 2, HELP_CATALOG_ENTRY)?;
+    let name = pa(arguments, 3, HELP_CATALOG_MAP_NAME)?.to_lowercase();
+    match action.to_lowercase().as_str() {
+        "add" => {
+            config.update_catalog(|catalog| catalog.add_map(name.clone()))?;
+            Ok(format!("Added map \"{}\" to the catalog", name))
+        }
+        "remove" => {
+            config.update_catalog(|catalog| catalog.remove_map(&name))?;
+            Ok(format!("Removed map \"{}\" from the catalog (bundled maps can't be removed)", name))
+        }
+        x => invalid_argument(x, HELP_CATALOG_ENTRY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(name: &str, teams: bool) -> ModeEntry {
+        ModeEntry { name: name.to_string(), teams, description: "".to_string() }
+    }
+
+    #[test]
+    fn bundled_catalog_parses() {
+        let catalog = Catalog::bundled();
+        assert!(!catalog.modes.is_empty());
+        assert!(!catalog.maps.is_empty());
+    }
+
+    #[test]
+    fn merge_replaces_modes_with_the_same_name() {
+        let bundled = Catalog { modes: vec![mode("SND", true), mode("DM", false)], ..Catalog::default() };
+        let extension = Catalog { modes: vec![mode("dm", true), mode("KOTH", true)], ..Catalog::default() };
+        let merged = bundled.merge(&extension);
+        let names: Vec<&str> = merged.modes.iter().map(|mode| mode.name.as_str()).collect();
+        assert_eq!(names, vec!["SND", "dm", "KOTH"]);
+        assert!(merged.is_team_mode(&GameMode::named("DM")));
+    }
+
+    #[test]
+    fn merge_adds_maps_once() {
+        let bundled = Catalog { maps: vec!["datacenter".to_string()], ..Catalog::default() };
+        let extension = Catalog { maps: vec!["DataCenter".to_string(), "sand".to_string()], ..Catalog::default() };
+        let merged = bundled.merge(&extension);
+        assert_eq!(merged.maps, vec!["datacenter".to_string(), "sand".to_string()]);
+    }
+
+    #[test]
+    fn merge_replaces_item_categories() {
+        let category = |name: &str, item: &str| ItemCategory { name: name.to_string(), items: vec![item.to_string()] };
+        let bundled = Catalog { item_categories: vec![category("pistols", "glock")], ..Catalog::default() };
+        let extension = Catalog { item_categories: vec![category("Pistols", "deagle")], ..Catalog::default() };
+        let merged = bundled.merge(&extension);
+        assert_eq!(merged.item_categories.len(), 1);
+        assert_eq!(merged.find_item_category("pistols").unwrap().items, vec!["deagle".to_string()]);
+    }
+}
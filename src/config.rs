@@ -2,15 +2,29 @@ use std::env::var;
 use rand;
 use rand::seq::{IteratorRandom};
 use crate::pavlov::{GameMode};
+use crate::catalog::Catalog;
+use crate::pools::{MapPool, PoolSchedule, unknown_pool};
+use crate::votekick::{VoteKickSettings, TempBan};
+use crate::links::Link;
+use crate::whitelist::Whitelist;
+use crate::rules::RuleSettings;
+use crate::autobalance::AutoBalance;
+use crate::economy::EconomyPreset;
+use crate::mapactions::MapAction;
+use crate::skins::{SkinTheme, ModeTheme, bundled_themes};
+use crate::workshop::WorkshopMetadata;
 use derive_more::{Display};
 use core::{fmt};
-use crate::model::{BotErrorKind, IvanError};
+use crate::model::{BotErrorKind, IvanError, check_alias};
 use serde::{Deserialize, Serialize};
 use std::{fs};
-use serde_json::{to_string_pretty, from_str};
+use serde_json::{to_string_pretty, from_str, from_value, Value};
+use crate::migration::{migrate, CONFIG_VERSION};
+use crate::storage::get_storage;
 use dirs::home_dir;
 use std::fmt::Formatter;
 use std::cmp::min;
+use std::path::Path;
 
 const IVAN_CONFIG: &str = "ivan.json";
 
@@ -27,6 +41,12 @@ pub struct Player {
     pub(crate) UniqueId: String,
 }
 
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+pub struct Items {
+    pub(crate) ItemList: Vec<String>,
+}
+
 #[allow(non_snake_case)]
 #[derive(Deserialize)]
 pub struct PlayerInfoContainer {
@@ -45,6 +65,37 @@ pub struct PlayerInfo {
 
 }
 
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+pub struct ServerInfoContainer {
+    pub(crate) ServerInfo: ServerInfo,
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Clone)]
+pub struct ServerInfo {
+    #[serde(default)]
+    pub(crate) MapLabel: String,
+    #[serde(default)]
+    pub(crate) GameMode: String,
+    /// Started while a round is played, SND has a pause between rounds.
+    #[serde(default)]
+    pub(crate) RoundState: String,
+    #[serde(default, deserialize_with = "deserialize_text")]
+    pub(crate) Team0Score: String,
+    #[serde(default, deserialize_with = "deserialize_text")]
+    pub(crate) Team1Score: String,
+}
+
+/// Some server versions send the scores as numbers, others as strings.
+fn deserialize_text<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(value) => value,
+        Value::Null => String::new(),
+        value => value.to_string()
+    })
+}
+
 
 #[derive(Serialize, Deserialize)]
 pub struct IvanConfig {
@@ -58,6 +109,13 @@ pub struct IvanConfig {
     #[serde(default)]
     skin_shuffle: bool,
 
+    /// Skin themes added by admins, on top of the bundled themes.
+    #[serde(default)]
+    skin_themes: Vec<SkinTheme>,
+
+    #[serde(default)]
+    mode_themes: Vec<ModeTheme>,
+
     #[serde(default)]
     gun_mode: GunMode,
 
@@ -66,6 +124,67 @@ pub struct IvanConfig {
 
     #[serde(default = "default_option_none")]
     team_channels: Option<(u64, u64)>,
+
+    #[serde(default)]
+    raw_filter_mode: RawFilterMode,
+
+    #[serde(default)]
+    raw_filter: Vec<String>,
+
+    #[serde(default)]
+    catalog: Catalog,
+
+    /// The bundled catalog merged with `catalog`, rebuilt when the config is read and when `catalog` changes.
+    #[serde(skip)]
+    merged_catalog: Catalog,
+
+    #[serde(default)]
+    workshop: Vec<WorkshopMetadata>,
+
+    #[serde(default)]
+    log_channel: Option<u64>,
+
+    /// Voice channel `-team merge` moves the team channels back into.
+    #[serde(default)]
+    lobby_channel: Option<u64>,
+
+    #[serde(default)]
+    pools: Vec<MapPool>,
+
+    /// Votes only use the maps of this pool, all pool maps when it is not set.
+    #[serde(default)]
+    active_pool: Option<String>,
+
+    #[serde(default)]
+    schedules: Vec<PoolSchedule>,
+
+    #[serde(default)]
+    votekick: VoteKickSettings,
+
+    /// Bans from kick votes that still have to be lifted.
+    #[serde(default)]
+    temp_bans: Vec<TempBan>,
+
+    /// Discord accounts with the steam account they proved to own.
+    #[serde(default)]
+    links: Vec<Link>,
+
+    #[serde(default)]
+    whitelist: Whitelist,
+
+    #[serde(default)]
+    rules: RuleSettings,
+
+    #[serde(default)]
+    autobalance: AutoBalance,
+
+    #[serde(default)]
+    economy: Vec<EconomyPreset>,
+
+    /// Set when the config file on disk could not be read, writes are refused so the file is not
+    /// replaced by an empty config.
+    #[serde(skip)]
+    read_only: bool,
 }
 
 fn default_option_none() -> Option<(u64,u64)> {
@@ -76,6 +195,10 @@ fn default_vote_count() -> u64 {
     8
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Display, Eq, PartialEq)]
 pub enum GunMode {
     Modern,
@@ -90,21 +213,110 @@ impl Default for GunMode {
     }
 }
 
+/// Decides how `raw_filter` is applied to the verb of a raw RCON command.
+#[derive(Serialize, Deserialize, Clone, Copy, Display, Eq, PartialEq)]
+pub enum RawFilterMode {
+    Allow,
+    Deny,
+}
+
+impl Default for RawFilterMode {
+    fn default() -> RawFilterMode {
+        RawFilterMode::Deny
+    }
+}
+
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PoolMap {
     pub map: String,
     pub gamemode: GameMode,
     pub alias: String,
+    /// Disabled maps stay in the pool but are left out of votes.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Name of the economy preset used while this map runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub economy: Option<String>,
+    /// Run once the map is running on the server.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<MapAction>,
+}
+
+impl PoolMap {
+    pub fn new(map: String, gamemode: GameMode, alias: String) -> PoolMap {
+        PoolMap { map, gamemode, alias, enabled: true, economy: None, actions: vec![] }
+    }
+
+    /// A pool map is found by its alias or its map name.
+    pub fn matches(&self, alias_or_map: &str) -> bool {
+        self.alias.eq_ignore_ascii_case(alias_or_map) || self.map.eq_ignore_ascii_case(alias_or_map)
+    }
 }
 
 impl fmt::Display for PoolMap {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "map: \"{}\" \t({}) \tgamemode: {}", self.alias, self.map, self.gamemode)
+        write!(f, "map: \"{}\" \t({}) \tgamemode: {}", self.alias, self.map, self.gamemode)?;
+        if !self.enabled {
+            write!(f, " \t(disabled)")?;
+        }
+        Ok(())
     }
 }
 
 impl IvanConfig {
+    /// An empty config that refuses to be written, used when the config file exists but is unreadable.
+    pub fn read_only() -> IvanConfig {
+        IvanConfig { read_only: true, ..IvanConfig::default() }
+    }
+
+    /// Replaces the whole config, for example with a restored backup, and writes it. The write
+    /// backs up the replaced config so the replacement can be undone.
+    pub fn replace(&mut self, config: IvanConfig) -> Result<(), IvanError> {
+        *self = config;
+        write_config(&self)
+    }
+
+    /// Checks what the commands enforce on a config that did not go through them, like an import:
+    /// valid and unique aliases, and pools, schedules and pool maps that point to existing entries.
+    pub fn validate(&self) -> Result<(), IvanError> {
+        for (index, (alias, _)) in self.aliases.iter().enumerate() {
+            check_alias(alias)?;
+            if self.aliases[..index].iter().any(|(other, _)| other.eq_ignore_ascii_case(alias)) {
+                return Err(IvanError { input: format!("alias \"{}\" exists more than once", alias), kind: BotErrorKind::InvalidMapAlias });
+            }
+        }
+        for map in &self.maps {
+            check_alias(&map.alias)?;
+            let other = self.resolve_alias(&map.alias).or_else(|| {
+                self.maps.iter().find(|other| other.alias.eq_ignore_ascii_case(&map.alias)).map(|other| other.map.clone())
+            });
+            if other.map(|other| other != map.map).unwrap_or(false) {
+                return Err(IvanError { input: format!("alias \"{}\" is used for more than one map", map.alias), kind: BotErrorKind::InvalidMapAlias });
+            }
+            if let Some(preset) = map.economy.as_ref().filter(|preset| self.find_economy_preset(preset).is_none()) {
+                return Err(IvanError { input: format!("pool map \"{}\" uses the economy preset \"{}\" which does not exist", map.alias, preset), kind: BotErrorKind::InvalidArgument });
+            }
+        }
+        for (index, pool) in self.pools.iter().enumerate() {
+            if pool.name.eq_ignore_ascii_case("all") || self.pools[..index].iter().any(|other| other.name.eq_ignore_ascii_case(&pool.name)) {
+                return Err(IvanError { input: format!("pool \"{}\" exists more than once", pool.name), kind: BotErrorKind::InvalidArgument });
+            }
+            if let Some(alias) = pool.maps.iter().find(|alias| !self.maps.iter().any(|map| map.alias.eq_ignore_ascii_case(alias))) {
+                return Err(IvanError { input: format!("pool \"{}\" contains \"{}\" which is not a pool map", pool.name, alias), kind: BotErrorKind::InvalidArgument });
+            }
+        }
+        if let Some(name) = self.schedules.iter().map(|schedule| &schedule.pool).chain(self.active_pool.iter()).find(|name| self.find_pool(name).is_none()) {
+            return Err(unknown_pool(name));
+        }
+        for (index, preset) in self.economy.iter().enumerate() {
+            if self.economy[..index].iter().any(|other| other.name.eq_ignore_ascii_case(&preset.name)) {
+                return Err(IvanError { input: format!("economy preset \"{}\" exists more than once", preset.name), kind: BotErrorKind::InvalidArgument });
+            }
+        }
+        Ok(())
+    }
+
     pub fn is_admin(&self, id: u64) -> bool {
         let admin = var("ADMIN_ID");
         if admin.is_ok() {
@@ -145,16 +357,47 @@ impl IvanConfig {
         self.mods.retain(|item| { *item != id });
         write_config(&self)
     }
+    /// Adds an alias, an alias that already points to `mapname` is left as it is.
     pub fn add_alias(&mut self, alias: String, mapname: String) -> Result<(), IvanError> {
-        self.aliases.push((alias, mapname));
-        write_config(&self)
+        match self.resolve_alias(&alias) {
+            Some(existing) if existing == mapname => Ok(()),
+            Some(existing) => Err(IvanError {
+                input: format!("alias \"{}\" already points to \"{}\", use -alias rename or -alias remove first", alias, existing),
+                kind: BotErrorKind::InvalidMapAlias,
+            }),
+            None => {
+                self.aliases.push((alias, mapname));
+                write_config(&self)
+            }
+        }
     }
     pub fn remove_alias(&mut self, alias: String) -> Result<(), IvanError> {
-        self.aliases.retain(|(key, value)| {
-            *key.to_lowercase() != alias.to_lowercase() && *value != alias
+        self.aliases.retain(|(key, _)| {
+            !key.eq_ignore_ascii_case(&alias)
         });
         write_config(&self)
     }
+    /// Renames an alias and the pool maps that use it.
+    pub fn rename_alias(&mut self, alias: &str, new_alias: String) -> Result<(), IvanError> {
+        if self.resolve_alias(alias).is_none() {
+            return Err(IvanError { input: alias.to_string(), kind: BotErrorKind::InvalidMapAlias });
+        }
+        if !alias.eq_ignore_ascii_case(&new_alias) && self.resolve_alias(&new_alias).is_some() {
+            return Err(IvanError { input: format!("alias \"{}\" already exists", new_alias), kind: BotErrorKind::InvalidMapAlias });
+        }
+        for (key, _) in self.aliases.iter_mut().filter(|(key, _)| key.eq_ignore_ascii_case(alias)) {
+            *key = new_alias.clone();
+        }
+        for map in self.maps.iter_mut().filter(|map| map.alias.eq_ignore_ascii_case(alias)) {
+            map.alias = new_alias.clone();
+        }
+        self.rename_in_pools(alias, &new_alias);
+        write_config(&self)
+    }
+    /// Pool maps that are listed under `alias`.
+    pub fn get_alias_users(&self, alias: &str) -> Vec<&PoolMap> {
+        self.maps.iter().filter(|map| map.alias.eq_ignore_ascii_case(alias)).collect()
+    }
     pub fn resolve_alias(&self, alias: &str) -> Option<String> {
         self.aliases.iter().find(|(key, _)| {
             *key.to_lowercase() == alias.to_lowercase()
@@ -163,6 +406,13 @@ impl IvanConfig {
         })
     }
 
+    pub fn get_lobby_channel(&self) -> Option<u64> {
+        self.lobby_channel
+    }
+    pub fn set_lobby_channel(&mut self, channel: u64) -> Result<(), IvanError> {
+        self.lobby_channel = Some(channel);
+        write_config(&self)
+    }
     pub fn get_team_channels(&self) -> Option<(u64, u64)> {
         self.team_channels
     }
@@ -171,31 +421,132 @@ impl IvanConfig {
         write_config(&self)
     }
     pub fn add_map(&mut self, map: String, gamemode: GameMode, alias: String) -> Result<(), IvanError> {
-        self.maps.push(PoolMap { map, gamemode, alias });
+        self.maps.push(PoolMap::new(map, gamemode, alias));
+        write_config(&self)
+    }
+    /// Adds several maps and their aliases with a single write. Nothing is added when one of the
+    /// aliases is taken by another map.
+    pub fn add_maps(&mut self, maps: Vec<PoolMap>) -> Result<(), IvanError> {
+        for (index, map) in maps.iter().enumerate() {
+            let taken = match self.resolve_alias(&map.alias) {
+                Some(existing) => existing != map.map,
+                None => maps[..index].iter().any(|other| other.alias.eq_ignore_ascii_case(&map.alias) && other.map != map.map)
+            };
+            if taken {
+                return Err(IvanError { input: format!("alias \"{}\" is already used for another map", map.alias), kind: BotErrorKind::InvalidMapAlias });
+            }
+        }
+        for map in maps {
+            if self.resolve_alias(&map.alias).is_none() {
+                self.aliases.push((map.alias.clone(), map.map.clone()));
+            }
+            self.maps.push(map);
+        }
+        write_config(&self)
+    }
+    pub fn find_pool_map(&self, alias_or_map: &str) -> Option<&PoolMap> {
+        self.maps.iter().find(|map| map.matches(alias_or_map))
+    }
+    /// Changes the game mode and/or alias of the pool maps found by `alias_or_map`.
+    pub fn edit_map(&mut self, alias_or_map: &str, gamemode: Option<GameMode>, alias: Option<String>) -> Result<(), IvanError> {
+        let current = self.find_pool_map(alias_or_map).map(|map| map.alias.clone()).ok_or_else(|| {
+            IvanError { input: alias_or_map.to_string(), kind: BotErrorKind::InvalidMapAlias }
+        })?;
+        if let Some(alias) = &alias {
+            if !alias.eq_ignore_ascii_case(&current) && self.resolve_alias(alias).is_some() {
+                return Err(IvanError { input: format!("alias \"{}\" already exists", alias), kind: BotErrorKind::InvalidMapAlias });
+            }
+        }
+        for map in self.maps.iter_mut().filter(|map| map.matches(alias_or_map)) {
+            if let Some(gamemode) = &gamemode {
+                map.gamemode = gamemode.clone();
+            }
+        }
+        if let Some(alias) = alias {
+            for (key, _) in self.aliases.iter_mut().filter(|(key, _)| key.eq_ignore_ascii_case(&current)) {
+                *key = alias.clone();
+            }
+            for map in self.maps.iter_mut().filter(|map| map.alias.eq_ignore_ascii_case(&current)) {
+                map.alias = alias.clone();
+            }
+            self.rename_in_pools(&current, &alias);
+        }
         write_config(&self)
     }
+    pub fn set_map_enabled(&mut self, alias_or_map: &str, enabled: bool) -> Result<(), IvanError> {
+        if self.find_pool_map(alias_or_map).is_none() {
+            return Err(IvanError { input: alias_or_map.to_string(), kind: BotErrorKind::InvalidMapAlias });
+        }
+        for map in self.maps.iter_mut().filter(|map| map.matches(alias_or_map)) {
+            map.enabled = enabled;
+        }
+        write_config(&self)
+    }
+    /// The one pool map with this alias, or with this map and game mode. A map in the pool with
+    /// several game modes needs the game mode.
+    pub fn find_single_pool_map(&self, alias_or_map: &str, gamemode: Option<&GameMode>) -> Result<&PoolMap, IvanError> {
+        self.single_pool_map_index(alias_or_map, gamemode).map(|index| &self.maps[index])
+    }
+    fn single_pool_map_index(&self, alias_or_map: &str, gamemode: Option<&GameMode>) -> Result<usize, IvanError> {
+        if let Some(index) = self.maps.iter().position(|map| map.alias.eq_ignore_ascii_case(alias_or_map) && gamemode.map_or(true, |gamemode| &map.gamemode == gamemode)) {
+            return Ok(index);
+        }
+        let found: Vec<usize> = self.maps.iter().enumerate().filter(|(_, map)| {
+            map.map.eq_ignore_ascii_case(alias_or_map) && gamemode.map_or(true, |gamemode| &map.gamemode == gamemode)
+        }).map(|(index, _)| index).collect();
+        match found.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(IvanError { input: alias_or_map.to_string(), kind: BotErrorKind::InvalidMapAlias }),
+            _ => {
+                let modes: Vec<String> = found.iter().map(|index| self.maps[*index].gamemode.to_string()).collect();
+                Err(IvanError { input: format!("{} is in the pool with the game modes {}, add the game mode or use the alias", alias_or_map, modes.join(", ")), kind: BotErrorKind::InvalidMapAlias })
+            }
+        }
+    }
+    /// Replaces the actions of the one pool map found by `find_single_pool_map`.
+    pub fn set_map_actions(&mut self, alias_or_map: &str, gamemode: Option<&GameMode>, actions: Vec<MapAction>) -> Result<(), IvanError> {
+        let index = self.single_pool_map_index(alias_or_map, gamemode)?;
+        self.maps[index].actions = actions;
+        write_config(&self)
+    }
+    /// Removes the pool maps with this alias or map, their aliases and their places in the map pools.
     pub fn remove_map(&mut self, alias: String) -> Result<(), IvanError> {
-        self.maps.retain(|map| {
-            map.alias != alias && map.map != alias
+        let removed: Vec<String> = self.maps.iter().filter(|map| map.matches(&alias)).map(|map| map.alias.clone()).collect();
+        self.maps.retain(|map| !map.matches(&alias));
+        self.aliases.retain(|(key, _)| {
+            !key.eq_ignore_ascii_case(&alias) && !removed.iter().any(|removed| removed.eq_ignore_ascii_case(key))
         });
+        for pool in self.pools.iter_mut() {
+            pool.maps.retain(|value| !removed.iter().any(|removed| removed.eq_ignore_ascii_case(value)));
+        }
         write_config(&self)
     }
+    /// The pool map the server is running, preferring the one with the running game mode.
+    pub fn find_running_map(&self, map: &str, gamemode: &str) -> Option<&PoolMap> {
+        let mut maps = self.maps.iter().filter(|pool_map| pool_map.map.eq_ignore_ascii_case(map));
+        let first = maps.clone().next();
+        maps.find(|pool_map| pool_map.gamemode.to_string().eq_ignore_ascii_case(gamemode)).or(first)
+    }
     pub fn get_maps_random(&self, game_mode: Option<GameMode>) -> Result<Vec<&PoolMap>, IvanError> {
         if self.maps.len() < 1 {
             return Err(IvanError { input: format!("there were no maps in the pool"), kind: BotErrorKind::InvalidVoteAmount });
         }
+        let catalog = self.get_catalog();
         let filtered_maps: Vec<&PoolMap> = self.maps.iter().filter(|map| {
-            match game_mode {
+            if !map.enabled || !catalog.has_mode(&map.gamemode) || !self.in_active_pool(map) {
+                return false;
+            }
+            match &game_mode {
                 Some(value) => {
-                    if value == GameMode::GUN {
+                    if value.is(GameMode::GUN) {
                         match self.gun_mode {
-                            GunMode::Random => vec![GameMode::GUN, GameMode::WW2GUN].contains(&map.gamemode),
-                            GunMode::WW2 => value == GameMode::WW2GUN,
-                            GunMode::OitcRandom => vec![GameMode::GUN, GameMode::WW2GUN, GameMode::OITC].contains(&map.gamemode),
-                            GunMode::Modern => value == GameMode::GUN
+                            GunMode::Random => map.gamemode.is(GameMode::GUN) || map.gamemode.is(GameMode::WW2GUN),
+                            GunMode::OitcRandom => map.gamemode.is(GameMode::GUN) || map.gamemode.is(GameMode::WW2GUN) || map.gamemode.is(GameMode::OITC),
+                            GunMode::WW2 => value.is(GameMode::WW2GUN),
+                            GunMode::Modern => value.is(GameMode::GUN)
                         }
                     } else {
-                        value == map.gamemode
+                        *value == map.gamemode
                     }
                 }
                 None => true
@@ -215,6 +566,16 @@ impl IvanConfig {
         let value = filtered_maps.iter().map(|value| { *value }).choose_multiple(&mut rand::thread_rng(), amount);
         return Ok(value.clone());
     }
+    /// The game modes that have enough maps in the (active) pool for a map vote.
+    pub fn get_vote_modes(&self) -> Vec<GameMode> {
+        let mut modes: Vec<GameMode> = vec![];
+        for map in self.maps.iter() {
+            if !modes.contains(&map.gamemode) && self.get_maps_random(Some(map.gamemode.clone())).is_ok() {
+                modes.push(map.gamemode.clone());
+            }
+        }
+        modes
+    }
     pub fn get_maps(&self) -> &Vec<PoolMap> {
         &self.maps
     }
@@ -238,6 +599,16 @@ impl IvanConfig {
         write_config(&self)
     }
 
+    /// The channel for messages the bot sends on its own, falls back to the locked channel.
+    pub fn get_log_channel(&self) -> Option<u64> {
+        self.log_channel.or(self.channel_lock)
+    }
+
+    pub fn set_log_channel(&mut self, channel_id: Option<u64>) -> Result<(), IvanError> {
+        self.log_channel = channel_id;
+        write_config(&self)
+    }
+
     pub fn set_skin_shuffle(&mut self, value: bool) -> Result<(), IvanError> {
         self.skin_shuffle = value;
         write_config(&self)
@@ -247,6 +618,47 @@ impl IvanConfig {
         self.skin_shuffle
     }
 
+    /// The bundled skin themes with the stored themes, a stored theme replaces a bundled one with the same name.
+    pub fn get_skin_themes(&self) -> Vec<SkinTheme> {
+        let mut themes: Vec<SkinTheme> = bundled_themes().into_iter().filter(|theme| {
+            !self.skin_themes.iter().any(|stored| stored.name.eq_ignore_ascii_case(&theme.name))
+        }).collect();
+        themes.extend(self.skin_themes.iter().cloned());
+        themes
+    }
+    pub fn find_skin_theme(&self, name: &str) -> Option<SkinTheme> {
+        self.get_skin_themes().into_iter().find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+    pub fn save_skin_theme(&mut self, theme: SkinTheme) -> Result<(), IvanError> {
+        self.skin_themes.retain(|value| !value.name.eq_ignore_ascii_case(&theme.name));
+        self.skin_themes.push(theme);
+        write_config(&self)
+    }
+    /// Removes a stored theme, game modes that used a theme that is gone no longer apply one.
+    pub fn remove_skin_theme(&mut self, name: &str) -> Result<(), IvanError> {
+        if !self.skin_themes.iter().any(|theme| theme.name.eq_ignore_ascii_case(name)) {
+            return Err(IvanError { input: format!("\"{}\" is not a stored skin theme, bundled themes can't be removed", name), kind: BotErrorKind::InvalidArgument });
+        }
+        self.skin_themes.retain(|theme| !theme.name.eq_ignore_ascii_case(name));
+        let themes = self.get_skin_themes();
+        self.mode_themes.retain(|value| themes.iter().any(|theme| theme.name.eq_ignore_ascii_case(&value.theme)));
+        write_config(&self)
+    }
+    pub fn get_mode_themes(&self) -> &Vec<ModeTheme> {
+        &self.mode_themes
+    }
+    /// The skin theme applied after votes on `gamemode`.
+    pub fn get_mode_theme(&self, gamemode: &GameMode) -> Option<SkinTheme> {
+        self.mode_themes.iter().find(|value| value.gamemode == *gamemode).and_then(|value| self.find_skin_theme(&value.theme))
+    }
+    pub fn set_mode_theme(&mut self, gamemode: GameMode, theme: Option<String>) -> Result<(), IvanError> {
+        self.mode_themes.retain(|value| value.gamemode != gamemode);
+        if let Some(theme) = theme {
+            self.mode_themes.push(ModeTheme { gamemode, theme });
+        }
+        write_config(&self)
+    }
+
     pub fn set_gun_mode(&mut self, value: GunMode) -> Result<(), IvanError> {
         self.gun_mode = value;
         write_config(&self)
@@ -271,33 +683,332 @@ impl IvanConfig {
     pub fn get_vote_amount(&self) -> u64 {
         self.map_vote_count
     }
+
+    pub fn get_pools(&self) -> &Vec<MapPool> {
+        &self.pools
+    }
+    pub fn find_pool(&self, name: &str) -> Option<&MapPool> {
+        self.pools.iter().find(|pool| pool.name.eq_ignore_ascii_case(name))
+    }
+    pub fn create_pool(&mut self, name: String) -> Result<(), IvanError> {
+        if name.eq_ignore_ascii_case("all") || self.find_pool(&name).is_some() {
+            return Err(IvanError { input: format!("pool \"{}\" already exists", name), kind: BotErrorKind::InvalidArgument });
+        }
+        self.pools.push(MapPool { name, maps: vec![] });
+        write_config(&self)
+    }
+    /// Deletes a pool with its schedules, votes use all maps again when it was the active pool.
+    pub fn delete_pool(&mut self, name: &str) -> Result<(), IvanError> {
+        if self.find_pool(name).is_none() {
+            return Err(unknown_pool(name));
+        }
+        self.pools.retain(|pool| !pool.name.eq_ignore_ascii_case(name));
+        self.schedules.retain(|schedule| !schedule.pool.eq_ignore_ascii_case(name));
+        if self.active_pool.as_ref().map(|active| active.eq_ignore_ascii_case(name)).unwrap_or(false) {
+            self.active_pool = None;
+        }
+        write_config(&self)
+    }
+    pub fn add_to_pool(&mut self, name: &str, aliases: Vec<String>) -> Result<(), IvanError> {
+        let aliases = aliases.into_iter().map(|alias| {
+            self.find_pool_map(&alias).map(|map| map.alias.clone()).ok_or(IvanError { input: alias, kind: BotErrorKind::InvalidMapAlias })
+        }).collect::<Result<Vec<String>, IvanError>>()?;
+        let pool = self.pools.iter_mut().find(|pool| pool.name.eq_ignore_ascii_case(name)).ok_or_else(|| unknown_pool(name))?;
+        for alias in aliases {
+            if !pool.contains(&alias) {
+                pool.maps.push(alias);
+            }
+        }
+        write_config(&self)
+    }
+    pub fn remove_from_pool(&mut self, name: &str, aliases: Vec<String>) -> Result<(), IvanError> {
+        let pool = self.pools.iter_mut().find(|pool| pool.name.eq_ignore_ascii_case(name)).ok_or_else(|| unknown_pool(name))?;
+        pool.maps.retain(|value| !aliases.iter().any(|alias| alias.eq_ignore_ascii_case(value)));
+        write_config(&self)
+    }
+    pub fn get_active_pool(&self) -> Option<&str> {
+        self.active_pool.as_deref()
+    }
+    pub fn use_pool(&mut self, name: Option<String>) -> Result<(), IvanError> {
+        self.active_pool = match name {
+            Some(name) => Some(self.find_pool(&name).map(|pool| pool.name.clone()).ok_or_else(|| unknown_pool(&name))?),
+            None => None
+        };
+        write_config(&self)
+    }
+    pub fn get_schedules(&self) -> &Vec<PoolSchedule> {
+        &self.schedules
+    }
+    pub fn add_schedule(&mut self, schedule: PoolSchedule) -> Result<(), IvanError> {
+        if self.find_pool(&schedule.pool).is_none() {
+            return Err(unknown_pool(&schedule.pool));
+        }
+        self.schedules.push(schedule);
+        write_config(&self)
+    }
+    /// Removes schedule `number` as shown by `-pool schedule list`, starting at 1.
+    pub fn remove_schedule(&mut self, number: usize) -> Result<(), IvanError> {
+        if number < 1 || number > self.schedules.len() {
+            return Err(IvanError { input: format!("there is no schedule {}", number), kind: BotErrorKind::InvalidArgument });
+        }
+        self.schedules.remove(number - 1);
+        write_config(&self)
+    }
+    fn in_active_pool(&self, map: &PoolMap) -> bool {
+        match &self.active_pool {
+            Some(name) => self.find_pool(name).map(|pool| pool.contains(&map.alias)).unwrap_or(true),
+            None => true
+        }
+    }
+    fn rename_in_pools(&mut self, alias: &str, new_alias: &str) {
+        for value in self.pools.iter_mut().flat_map(|pool| pool.maps.iter_mut()).filter(|value| value.eq_ignore_ascii_case(alias)) {
+            *value = new_alias.to_string();
+        }
+    }
+
+    pub fn get_economy_presets(&self) -> &Vec<EconomyPreset> {
+        &self.economy
+    }
+    pub fn find_economy_preset(&self, name: &str) -> Option<&EconomyPreset> {
+        self.economy.iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+    /// Adds the preset or replaces the preset with the same name.
+    pub fn save_economy_preset(&mut self, preset: EconomyPreset) -> Result<(), IvanError> {
+        match self.economy.iter_mut().find(|value| value.name.eq_ignore_ascii_case(&preset.name)) {
+            Some(value) => *value = preset,
+            None => self.economy.push(preset)
+        }
+        write_config(&self)
+    }
+    /// Deletes a preset, the pool maps that used it get no economy.
+    pub fn delete_economy_preset(&mut self, name: &str) -> Result<(), IvanError> {
+        self.economy.retain(|preset| !preset.name.eq_ignore_ascii_case(name));
+        for map in self.maps.iter_mut().filter(|map| map.economy.as_ref().map(|value| value.eq_ignore_ascii_case(name)).unwrap_or(false)) {
+            map.economy = None;
+        }
+        write_config(&self)
+    }
+    pub fn set_map_economy(&mut self, aliases: &[String], preset: Option<String>) -> Result<(), IvanError> {
+        if let Some(alias) = aliases.iter().find(|alias| self.find_pool_map(alias).is_none()) {
+            return Err(IvanError { input: alias.to_string(), kind: BotErrorKind::InvalidMapAlias });
+        }
+        for map in self.maps.iter_mut().filter(|map| aliases.iter().any(|alias| map.matches(alias))) {
+            map.economy = preset.clone();
+        }
+        write_config(&self)
+    }
+
+    pub fn get_votekick(&self) -> &VoteKickSettings {
+        &self.votekick
+    }
+    pub fn set_votekick(&mut self, settings: VoteKickSettings) -> Result<(), IvanError> {
+        self.votekick = settings;
+        write_config(&self)
+    }
+    /// Players on the immune list and players linked to an admin or mod can't be vote kicked.
+    pub fn is_kick_immune(&self, steam_id: u64) -> bool {
+        self.votekick.immune.contains(&steam_id) || self.is_staff_player(steam_id)
+    }
+    /// Steam accounts linked to an admin or mod. Staff that didn't use `-link` can't be recognized in game.
+    pub fn is_staff_player(&self, steam_id: u64) -> bool {
+        self.find_discord_link(steam_id).map(|discord| self.is_admin(discord) || self.is_mod(discord)).unwrap_or(false)
+    }
+    /// Admins and mods without a linked steam account, in game they are treated like any other player.
+    pub fn unlinked_staff(&self) -> Vec<u64> {
+        let admin = var("ADMIN_ID").ok().and_then(|id| id.parse::<u64>().ok());
+        let mut staff: Vec<u64> = admin.into_iter().chain(self.admins.iter().cloned()).chain(self.mods.iter().cloned()).collect();
+        staff.sort_unstable();
+        staff.dedup();
+        staff.into_iter().filter(|discord| self.find_steam_link(*discord).is_none()).collect()
+    }
+    /// A discord account is banned while its linked steam account has a temporary ban.
+    pub fn is_banned_discord(&self, discord: u64) -> bool {
+        self.find_steam_link(discord).map(|steam| self.temp_bans.iter().any(|ban| ban.steam_id == steam)).unwrap_or(false)
+    }
+    pub fn get_temp_bans(&self) -> &Vec<TempBan> {
+        &self.temp_bans
+    }
+    pub fn add_temp_ban(&mut self, ban: TempBan) -> Result<(), IvanError> {
+        self.temp_bans.retain(|value| value.steam_id != ban.steam_id);
+        self.temp_bans.push(ban);
+        write_config(&self)
+    }
+    pub fn remove_temp_ban(&mut self, steam_id: u64) -> Result<(), IvanError> {
+        self.temp_bans.retain(|value| value.steam_id != steam_id);
+        write_config(&self)
+    }
+
+    pub fn get_links(&self) -> &Vec<Link> {
+        &self.links
+    }
+    pub fn find_steam_link(&self, discord: u64) -> Option<u64> {
+        self.links.iter().find(|link| link.discord == discord).map(|link| link.steam)
+    }
+    pub fn find_discord_link(&self, steam: u64) -> Option<u64> {
+        self.links.iter().find(|link| link.steam == steam).map(|link| link.discord)
+    }
+    /// Links a discord account, replacing earlier links of either account.
+    pub fn add_link(&mut self, link: Link) -> Result<(), IvanError> {
+        self.links.retain(|value| value.discord != link.discord && value.steam != link.steam);
+        self.links.push(link);
+        write_config(&self)
+    }
+    pub fn remove_link(&mut self, discord: u64) -> Result<(), IvanError> {
+        self.links.retain(|link| link.discord != discord);
+        write_config(&self)
+    }
+
+    pub fn get_whitelist(&self) -> &Whitelist {
+        &self.whitelist
+    }
+    pub fn set_whitelist(&mut self, whitelist: Whitelist) -> Result<(), IvanError> {
+        self.whitelist = whitelist;
+        write_config(&self)
+    }
+
+    pub fn get_rules(&self) -> &RuleSettings {
+        &self.rules
+    }
+    pub fn set_rules(&mut self, rules: RuleSettings) -> Result<(), IvanError> {
+        self.rules = rules;
+        write_config(&self)
+    }
+
+    pub fn get_autobalance(&self) -> &AutoBalance {
+        &self.autobalance
+    }
+    pub fn set_autobalance(&mut self, autobalance: AutoBalance) -> Result<(), IvanError> {
+        self.autobalance = autobalance;
+        write_config(&self)
+    }
+
+    /// The bundled catalog combined with the entries admins added at runtime.
+    pub fn get_catalog(&self) -> &Catalog {
+        &self.merged_catalog
+    }
+
+    pub fn update_catalog<F: FnOnce(&mut Catalog)>(&mut self, update: F) -> Result<(), IvanError> {
+        update(&mut self.catalog);
+        self.merged_catalog = Catalog::bundled().merge(&self.catalog);
+        write_config(&self)
+    }
+
+    /// Builds the merged catalog of a config that was just deserialized.
+    pub fn with_merged_catalog(mut self) -> IvanConfig {
+        self.merged_catalog = Catalog::bundled().merge(&self.catalog);
+        self
+    }
+
+    pub fn add_workshop_metadata(&mut self, metadata: Vec<WorkshopMetadata>) -> Result<(), IvanError> {
+        self.workshop.retain(|item| {
+            !metadata.iter().any(|value| value.id.eq_ignore_ascii_case(&item.id))
+        });
+        self.workshop.extend(metadata);
+        write_config(&self)
+    }
+
+    pub fn get_workshop_metadata(&self) -> &Vec<WorkshopMetadata> {
+        &self.workshop
+    }
+
+    pub fn find_workshop_metadata(&self, map: &str) -> Option<&WorkshopMetadata> {
+        self.workshop.iter().find(|metadata| metadata.id.eq_ignore_ascii_case(map))
+    }
+
+    /// Formats a pool map with its workshop title when the metadata is known.
+    pub fn format_pool_map(&self, map: &PoolMap) -> String {
+        match self.find_workshop_metadata(&map.map) {
+            Some(metadata) => format!("{} \t\"{}\"", map, metadata.title),
+            None => map.to_string()
+        }
+    }
+
+    pub fn set_raw_filter_mode(&mut self, mode: RawFilterMode) -> Result<(), IvanError> {
+        self.raw_filter_mode = mode;
+        write_config(&self)
+    }
+
+    pub fn get_raw_filter_mode(&self) -> RawFilterMode {
+        self.raw_filter_mode
+    }
+
+    pub fn add_raw_filter(&mut self, verb: String) -> Result<(), IvanError> {
+        self.raw_filter.retain(|item| { item.to_lowercase() != verb.to_lowercase() });
+        self.raw_filter.push(verb);
+        write_config(&self)
+    }
+
+    pub fn remove_raw_filter(&mut self, verb: String) -> Result<(), IvanError> {
+        self.raw_filter.retain(|item| { item.to_lowercase() != verb.to_lowercase() });
+        write_config(&self)
+    }
+
+    pub fn get_raw_filter(&self) -> &Vec<String> {
+        &self.raw_filter
+    }
+
+    pub fn is_raw_verb_allowed(&self, verb: &str) -> bool {
+        let listed = self.raw_filter.iter().any(|item| { item.to_lowercase() == verb.to_lowercase() });
+        match self.raw_filter_mode {
+            RawFilterMode::Allow => listed,
+            RawFilterMode::Deny => !listed
+        }
+    }
 }
 
 
 pub fn get_config() -> Result<IvanConfig, IvanError> {
-    let file = fs::read_to_string(get_path()).map_err(|err| {
+    get_storage().load()
+}
+
+/// Reads and migrates a config file. Returns the config, the version it was migrated from and
+/// the original contents of the file.
+pub fn read_config_file(path: &str) -> Result<(IvanConfig, Option<u8>, String), IvanError> {
+    let file = fs::read_to_string(path).map_err(|err| {
         IvanError { input: err.to_string(), kind: BotErrorKind::ReadConfigError }
     })?;
-    return from_str(file.as_str()).map_err(|err| {
+    let value: Value = from_str(file.as_str()).map_err(|err| {
         IvanError { input: err.to_string(), kind: BotErrorKind::DeserializeError }
-    });
+    })?;
+    let (value, migrated_from) = migrate(value)?;
+    let config: IvanConfig = from_value(value).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::DeserializeError }
+    })?;
+    Ok((config.with_merged_catalog(), migrated_from, file))
 }
 
-fn write_config(config: &IvanConfig) -> Result<(), IvanError> {
-    let values = to_string_pretty(&config).map_err(|err| {
+pub fn serialize_config(config: &IvanConfig) -> Result<String, IvanError> {
+    to_string_pretty(config).map_err(|err| {
         IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
-    })?;
-    fs::write(get_path(), values).map_err(|err| {
-        IvanError { input: err.to_string(), kind: BotErrorKind::WriteError }
     })
 }
 
+pub fn config_exists() -> bool {
+    get_storage().exists()
+}
+
+fn write_config(config: &IvanConfig) -> Result<(), IvanError> {
+    if config.read_only {
+        return Err(IvanError {
+            input: format!("{} could not be read when the bot started so it won't be overwritten, fix the file and restart the bot", get_path()),
+            kind: BotErrorKind::WriteError,
+        });
+    }
+    get_storage().save(config)
+}
+
 /// `MyConfig` implements `Default`
 impl ::std::default::Default for IvanConfig {
-    fn default() -> Self { Self { version: 3, admins: vec!(), mods: vec![], aliases: vec![], maps: vec![], channel_lock: None, skin_shuffle: false, gun_mode: GunMode::Modern, map_vote_count: 8, team_channels: None } }
+    fn default() -> Self { Self { version: CONFIG_VERSION, admins: vec!(), mods: vec![], aliases: vec![], maps: vec![], channel_lock: None, skin_shuffle: false, skin_themes: vec![], mode_themes: vec![], gun_mode: GunMode::Modern, map_vote_count: 8, team_channels: None, raw_filter_mode: RawFilterMode::Deny, raw_filter: vec![], catalog: Catalog::default(), merged_catalog: Catalog::bundled(), workshop: vec![], log_channel: None, lobby_channel: None, pools: vec![], active_pool: None, schedules: vec![], votekick: VoteKickSettings::default(), temp_bans: vec![], links: vec![], whitelist: Whitelist::default(), rules: RuleSettings::default(), autobalance: AutoBalance::default(), economy: vec![], read_only: false } }
+}
+
+/// Resolves a file that lives in the same directory as the config file.
+pub fn get_data_path(file: &str) -> String {
+    let path = get_path();
+    Path::new(&path).with_file_name(file).to_str().map(|value| value.to_string()).unwrap_or_else(|| file.to_string())
 }
 
-fn get_path() -> String {
+pub fn get_path() -> String {
     return var("CONFIG_PATH").unwrap_or_else(|_| {
         let dir = home_dir();
         match dir {
@@ -0,0 +1,96 @@
+use serenity::client::Context;
+use serenity::model::channel::Message;
+use serenity::http::CacheHttp;
+use crate::backup::{list_backups, format_age};
+use crate::config::{read_config_file, IvanConfig};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument};
+use crate::output::output;
+use crate::pavlov::{pa, parse_number};
+use crate::transfer::{export, dry_run, parse_sections, ImportMode};
+use crate::audit::audit;
+use crate::background::reload_config;
+use crate::help::{HELP_CONFIG_ARGUMENT, HELP_CONFIG_BACKUP, HELP_CONFIG_IMPORT};
+
+pub fn handle_config(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &mut Context) -> Result<(), IvanError> {
+    let author = msg.author.id.0;
+    let response = match pa(arguments, 1, HELP_CONFIG_ARGUMENT)?.to_lowercase().as_str() {
+        "backups" => handle_backups()?,
+        "restore" => handle_restore(arguments, &mut framework.config, author)?,
+        "export" => return handle_export(arguments, &framework.config, msg, ctx),
+        "import" => handle_import(arguments, framework, msg, author)?,
+        "reload" => {
+            reload_config(framework)?;
+            audit(author, "reloaded the config from disk".to_string());
+            "Reloaded the config from disk".to_string()
+        }
+        x => invalid_argument(x, HELP_CONFIG_ARGUMENT)?
+    };
+    output(ctx, msg, response);
+    Ok(())
+}
+
+fn handle_backups() -> Result<String, IvanError> {
+    let backups = list_backups()?;
+    if backups.is_empty() {
+        return Ok("There are no config backups yet, one is made before every config change".to_string());
+    }
+    Ok(backups.iter().enumerate().fold("Config backups (newest first):".to_string(), |a, (index, backup)| {
+        format!("{}\n{}: {}", a, index + 1, format_age(backup.created))
+    }))
+}
+
+fn handle_restore(arguments: &Vec<&str>, config: &mut IvanConfig, author: u64) -> Result<String, IvanError> {
+    let number: usize = parse_number(pa(arguments, 2, HELP_CONFIG_BACKUP)?)?;
+    let backups = list_backups()?;
+    let backup = number.checked_sub(1).and_then(|index| backups.get(index)).ok_or_else(|| {
+        IvanError { input: format!("there is no backup {}, {}", number, HELP_CONFIG_BACKUP), kind: BotErrorKind::BackupError }
+    })?;
+    let (restored, _, _) = read_config_file(&backup.path.to_string_lossy())?;
+    config.replace(restored)?;
+    audit(author, format!("restored config backup {} from {}", number, format_age(backup.created)));
+    Ok(format!("Restored the config backup from {}, the replaced config was backed up as number 1", format_age(backup.created)))
+}
+
+fn handle_export(arguments: &[&str], config: &IvanConfig, msg: &mut Message, ctx: &mut Context) -> Result<(), IvanError> {
+    let sections = parse_sections(&arguments[2..])?;
+    let file = export(config, &sections)?;
+    msg.channel_id.send_files(ctx.http(), vec![(file.as_bytes(), "ivan-export.json")], |m| {
+        m.content(format!("Exported sections: {}", sections.join(", ")))
+    }).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::CouldNotReply }
+    })?;
+    Ok(())
+}
+
+/// `-config import [merge, replace] (sections)` shows a dry run of the attached file,
+/// `-config import confirm` applies it and `-config import cancel` forgets it.
+fn handle_import(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &Message, author: u64) -> Result<String, IvanError> {
+    let mode = match pa(arguments, 2, HELP_CONFIG_IMPORT)?.to_lowercase().as_str() {
+        "merge" => ImportMode::Merge,
+        "replace" => ImportMode::Replace,
+        "confirm" => {
+            let pending = framework.pending_import.take().ok_or_else(|| {
+                IvanError { input: "there is no import waiting for confirmation".to_string(), kind: BotErrorKind::ImportError }
+            })?;
+            pending.apply(&mut framework.config)?;
+            audit(author, "imported a config file".to_string());
+            return Ok("Import applied, use -config restore 1 to undo it".to_string());
+        }
+        "cancel" => {
+            framework.pending_import = None;
+            return Ok("Import cancelled".to_string());
+        }
+        x => return invalid_argument(x, HELP_CONFIG_IMPORT)
+    };
+    let sections = parse_sections(&arguments[3..])?;
+    let attachment = msg.attachments.first().ok_or_else(|| {
+        IvanError { input: "attach an exported json file to the message".to_string(), kind: BotErrorKind::MissingArgument }
+    })?;
+    let bytes = attachment.download().map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::DiscordError }
+    })?;
+    let (pending, diff) = dry_run(&framework.config, &bytes, &sections, mode)?;
+    framework.pending_import = Some(pending);
+    Ok(format!("Dry run of the import:{}\nUse -config import confirm to apply it or -config import cancel", diff))
+}
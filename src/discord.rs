@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::time::Instant;
 use serenity::client::{Client, EventHandler};
 use serenity::model::channel::{Message};
 use serenity::prelude::{Context };
@@ -5,9 +7,12 @@ use serenity::framework::Framework;
 use std::process::exit;
 use std::env::{var};
 use crate::credentials::{get_login};
-use crate::config::{get_config, IvanConfig };
+use crate::config::{get_config, IvanConfig, config_exists};
 use crate::model::{handle_command, IvanError};
 use crate::voting::Vote;
+use crate::transfer::PendingImport;
+use crate::links::PendingLink;
+use crate::background::start_background;
 use std::sync::{Mutex, Arc};
 use crate::permissions::PermissionLevel;
 use crate::connect::{Connection,  create_connection_unwrap};
@@ -58,6 +63,12 @@ pub struct CustomFramework {
     pub connection: Connection,
     pub config: IvanConfig,
     pub vote: Option<Vote>,
+    pub items: Vec<String>,
+    pub pending_import: Option<PendingImport>,
+    /// When each discord user last started a kick vote.
+    pub kick_cooldowns: HashMap<u64, Instant>,
+    /// Links waiting for `-link confirm`, by discord user.
+    pub pending_links: HashMap<u64, PendingLink>,
 }
 
 
@@ -75,8 +86,14 @@ pub fn run_discord() {
         connection,
         config,
         vote: None,
+        items: vec![],
+        pending_import: None,
+        kick_cooldowns: HashMap::new(),
+        pending_links: HashMap::new(),
     }));
 
+    start_background(arc.clone(), client.cache_and_http.http.clone());
+
     let concurrent_framework = ConcurrentFramework {
         data: arc,
         cache: client.cache_and_http.cache.clone(),
@@ -92,8 +109,13 @@ pub fn run_discord() {
 fn recover_error(error: Result<IvanConfig, IvanError>) -> IvanConfig {
     match error {
         Err(err) => {
-            println!("Could not initialize config, recreating it because: {}", err);
-            IvanConfig::default()
+            if config_exists() {
+                println!("Could not read the config, it will not be overwritten until it is fixed: {}", err);
+                IvanConfig::read_only()
+            } else {
+                println!("Could not find a config, creating a new one: {}", err);
+                IvanConfig::default()
+            }
         }
         Ok(ivan) => ivan
     }
@@ -132,6 +154,9 @@ fn authenticate(msg: &Message, config: &IvanConfig) -> PermissionLevel {
     if config.is_mod(uid) {
         return PermissionLevel::Mod;
     }
+    if config.is_banned_discord(uid) {
+        return PermissionLevel::None;
+    }
     if IvanConfig::allow_users() {
         return PermissionLevel::User;
     }
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::audit::audit;
+use crate::background::announce;
+use crate::config::{IvanConfig, ServerInfo};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument, get_player_list};
+use crate::pavlov::{PavlovCommands, TeamId, pa, parse_number};
+use crate::help::{HELP_ECONOMY, HELP_ECONOMY_NAME, HELP_ECONOMY_SETTING, HELP_CASH, HELP_ALIAS};
+
+/// Cash handed out automatically while a pool map with this preset runs, 0 turns a payout off.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EconomyPreset {
+    pub name: String,
+    /// Given to both teams at every round start.
+    #[serde(default)]
+    pub round_cash: u32,
+    /// Every player's cash is set to this at the first round of the map.
+    #[serde(default)]
+    pub map_cash: u32,
+    /// Given to the team that is behind at the start of a round.
+    #[serde(default)]
+    pub losing_bonus: u32,
+}
+
+impl EconomyPreset {
+    fn describe(&self) -> String {
+        format!("{}: round start {}, map start {}, losing team bonus {}", self.name, self.round_cash, self.map_cash, self.losing_bonus)
+    }
+}
+
+/// What the background thread remembers for the economy between match events.
+#[derive(Default)]
+pub struct EconomyState {
+    /// Set on a map change, the map cash is paid at the next round start.
+    map_started: bool,
+}
+
+/// `-economy list`, `-economy [create, delete] name`, `-economy set name [round, map, losing] cash`
+/// and `-economy use {name/none} alias (alias ...)`.
+pub fn handle_economy(arguments: &Vec<&str>, config: &mut IvanConfig, author: u64) -> Result<String, IvanError> {
+    let response = match pa(arguments, 1, HELP_ECONOMY)?.to_lowercase().as_str() {
+        "list" => {
+            return Ok(config.get_economy_presets().iter().fold("Economy presets:".to_string(), |a, preset| {
+                let maps: Vec<String> = config.get_maps().iter().filter(|map| {
+                    map.economy.as_ref().map(|value| value.eq_ignore_ascii_case(&preset.name)).unwrap_or(false)
+                }).map(|map| map.alias.clone()).collect();
+                format!("{}\n{} \tmaps: {}", a, preset.describe(), if maps.is_empty() { "none".to_string() } else { maps.join(", ") })
+            }));
+        }
+        "create" => {
+            let name = pa(arguments, 2, HELP_ECONOMY_NAME)?;
+            if name.eq_ignore_ascii_case("none") || config.find_economy_preset(name).is_some() {
+                return Err(IvanError { input: format!("economy preset \"{}\" already exists", name), kind: BotErrorKind::InvalidArgument });
+            }
+            config.save_economy_preset(EconomyPreset { name: name.to_string(), round_cash: 0, map_cash: 0, losing_bonus: 0 })?;
+            format!("Created economy preset \"{}\", set the payouts with -economy set {} round cash", name, name)
+        }
+        "delete" => {
+            let name = pa(arguments, 2, HELP_ECONOMY_NAME)?;
+            find_preset(config, name)?;
+            config.delete_economy_preset(name)?;
+            format!("Deleted economy preset \"{}\"", name)
+        }
+        "set" => {
+            let mut preset = find_preset(config, pa(arguments, 2, HELP_ECONOMY_NAME)?)?.clone();
+            let setting = pa(arguments, 3, HELP_ECONOMY_SETTING)?.to_lowercase();
+            let cash: u32 = parse_number(pa(arguments, 4, HELP_CASH)?)?;
+            match setting.as_str() {
+                "round" => preset.round_cash = cash,
+                "map" => preset.map_cash = cash,
+                "losing" => preset.losing_bonus = cash,
+                x => return invalid_argument(x, HELP_ECONOMY_SETTING)
+            }
+            let response = format!("Economy preset {}", preset.describe());
+            config.save_economy_preset(preset)?;
+            response
+        }
+        "use" => {
+            let name = pa(arguments, 2, HELP_ECONOMY_NAME)?;
+            pa(arguments, 3, HELP_ALIAS)?;
+            let aliases: Vec<String> = arguments.iter().skip(3).map(|value| value.to_string()).collect();
+            if name.eq_ignore_ascii_case("none") {
+                config.set_map_economy(&aliases, None)?;
+                format!("{} no longer use an economy preset", aliases.join(", "))
+            } else {
+                let preset = find_preset(config, name)?.name.clone();
+                config.set_map_economy(&aliases, Some(preset.clone()))?;
+                format!("{} use economy preset \"{}\"", aliases.join(", "), preset)
+            }
+        }
+        x => return invalid_argument(x, HELP_ECONOMY)
+    };
+    audit(author, response.clone());
+    Ok(response)
+}
+
+fn find_preset<'a>(config: &'a IvanConfig, name: &str) -> Result<&'a EconomyPreset, IvanError> {
+    config.find_economy_preset(name).ok_or_else(|| {
+        IvanError { input: format!("there is no economy preset \"{}\", use -economy list", name), kind: BotErrorKind::InvalidArgument }
+    })
+}
+
+/// The preset of the pool map the server is running.
+fn running_preset(config: &IvanConfig, info: &ServerInfo) -> Option<EconomyPreset> {
+    let map = config.find_running_map(&info.MapLabel, &info.GameMode)?;
+    map.economy.as_ref().and_then(|name| config.find_economy_preset(name)).cloned()
+}
+
+pub fn economy_map_changed(state: &mut EconomyState) {
+    state.map_started = true;
+}
+
+/// Pays out the preset of the running map when a round starts.
+pub fn economy_round_started(framework: &mut CustomFramework, http: &Http, state: &mut EconomyState, info: &ServerInfo) {
+    let first_round = state.map_started;
+    state.map_started = false;
+    let preset = match running_preset(&framework.config, info) {
+        Some(preset) => preset,
+        None => return
+    };
+    let mut payouts: Vec<String> = vec![];
+    if first_round && preset.map_cash > 0 {
+        match get_player_list(framework) {
+            Ok(players) => {
+                for steam_id in players.iter().filter_map(|player| player.UniqueId.parse().ok()) {
+                    framework.connection.execute_command(PavlovCommands::SetCash(steam_id, preset.map_cash));
+                }
+                payouts.push(format!("set everyone's cash to {}", preset.map_cash));
+            }
+            Err(err) => println!("could not set the map cash: {}", err)
+        }
+    }
+    if preset.round_cash > 0 {
+        for team in &[0, 1] {
+            framework.connection.execute_command(PavlovCommands::GiveTeamCash(*team, preset.round_cash));
+        }
+        payouts.push(format!("gave both teams {}", preset.round_cash));
+    }
+    if preset.losing_bonus > 0 && !first_round {
+        if let Some(team) = losing_team(info) {
+            framework.connection.execute_command(PavlovCommands::GiveTeamCash(team, preset.losing_bonus));
+            payouts.push(format!("gave the {} team a bonus of {}", if team == 1 { "red" } else { "blue" }, preset.losing_bonus));
+        }
+    }
+    if payouts.is_empty() {
+        return;
+    }
+    let message = format!("Economy \"{}\": {}", preset.name, payouts.join(", "));
+    // one announcement per map, the payouts of every round would flood the log channel
+    if first_round {
+        announce(http, &framework.config, message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// The team with the lower score, none when the scores are even or unknown.
+fn losing_team(info: &ServerInfo) -> Option<TeamId> {
+    let blue: i64 = info.Team0Score.trim().parse().ok()?;
+    let red: i64 = info.Team1Score.trim().parse().ok()?;
+    match blue.cmp(&red) {
+        std::cmp::Ordering::Less => Some(0),
+        std::cmp::Ordering::Greater => Some(1),
+        std::cmp::Ordering::Equal => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matches::tests::server_info;
+
+    fn info(blue: &str, red: &str) -> ServerInfo {
+        server_info("datacenter", "Started", blue, red)
+    }
+
+    #[test]
+    fn the_team_with_the_lower_score_is_losing() {
+        assert_eq!(losing_team(&info("2", "5")), Some(0));
+        assert_eq!(losing_team(&info("6", "5")), Some(1));
+    }
+
+    #[test]
+    fn even_or_unknown_scores_have_no_losing_team() {
+        assert_eq!(losing_team(&info("3", "3")), None);
+        assert_eq!(losing_team(&info("", "3")), None);
+    }
+}
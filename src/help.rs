@@ -1,7 +1,8 @@
 
-pub const HELP_GAMEMODE: &str = "valid game modes are [WW2GUN, GUN, DM, KOTH, SND, TDM, OITC, TANKTDM]";
-pub const HELP_SKIN: &str = "valid skins are {Us, Soviet, German, Nato, Russian, Farmer, Naked, Prisoner, Clown}";
-pub const HELP_SKIN_TEAM: &str = "valid skins are [Us, Soviet, German, Nato, Russian, Farmer, Naked, Prisoner, Clown] or use random";
+pub const HELP_SKIN: &str = "valid skins are {Us, Soviet, German, Nato, Russian, Farmer, Naked, Prisoner, Clown, Cop}";
+pub const HELP_SKIN_TEAM: &str = "valid skins are [Us, Soviet, German, Nato, Russian, Farmer, Naked, Prisoner, Clown, Cop] or use random, shuffle, theme or team";
+pub const HELP_SKIN_THEME: &str = "specify a skin theme name or one of [list, add, remove, mode]";
+pub const HELP_SKIN_THEME_NAME: &str = "specify a skin theme name (use -skin theme list to find themes), mode takes none to stop applying a theme";
 pub const HELP_SKIN_MODE: &str = "valid arguments {true, false}";
 
 pub const HELP_STEAM_ID: &str = "valid steamId, looks like 76563192370220331";
@@ -10,7 +11,10 @@ pub const HELP_VALID_TEAM: &str = "valid teams are [0,1] (0 for blue, 1 for red)
 pub const HELP_MAP: &str = "specify a map (stalingrad),full steam workshop link or UGC1745146933 map code";
 pub const HELP_CASH : &str = "specify a cash amount";
 pub const HELP_LIMITED_AMMO : &str = "valid ammo options are the numbers 0-2";
-pub const HELP_ITEM : &str = "specify valid item id";
+pub const HELP_ITEM : &str = "specify valid item id or random followed by a category";
+pub const HELP_ITEM_TARGET : &str = "specify a steamId, team followed by 0 or 1, or all";
+pub const HELP_ITEM_CATEGORY : &str = "specify a valid item category, use -items categories to see them";
+pub const HELP_ITEMS_ARGUMENT : &str = "valid arguments are [refresh, categories]";
 
 pub const HELP_GUNMODE : &str = "valid are [modern, ww2, random, oitcRandom]";
 
@@ -18,15 +22,53 @@ pub const HELP_MOD_MODE : &str = "valid ones are {add, remove}";
 pub const HELP_ADMIN_MODE : &str = "valid ones are {add, remove}";
 
 
-pub const HELP_TEAM_MODES : &str = "valid team mode, valid ones are {channels}";
+pub const HELP_TEAM_MODES : &str = "valid team mode, valid ones are {channels, lobby, split, merge, swap, autobalance}";
 
 
-pub const HELP_MAP_ARGUMENT: &str = "valid map arguments are [add, remove, vote, list, default]";
-pub const HELP_CHANNEL_MODE: &str = "valid arguments are [lock, unlock]";
-pub const HELP_ALIAS_ARGUMENT: &str = "valid arguments are [add, remove, list]";
-pub const HELP_ALIAS: &str = "valid alias are a to z and numbers 0-9";
+pub const HELP_MAP_ARGUMENT: &str = "valid map arguments are [add, remove, edit, enable, disable, info, action, vote, list, default]";
+pub const HELP_MAP_ACTION: &str = "valid arguments are [add, remove, list] followed by the alias or map (and the game mode when the map is in the pool with several), remove takes the number shown by -map action list";
+pub const HELP_MAP_ACTION_TYPE: &str = "valid actions are [skin, ammo, cash, announce] followed by a skin (or random), an ammo type 0-2, a cash amount or a message";
+pub const HELP_MAP_EDIT: &str = "valid edit arguments are gamemode=X and alias=Y";
+pub const HELP_MAP_BULK: &str = "-map add gamemode {url/map} alias {url/map} alias ... needs a map and an alias for every map";
+pub const HELP_RAW_FILTER: &str = "valid arguments are [allow, deny, add, remove, list]";
+pub const HELP_RAW_VERB: &str = "specify the command verb, for example SwitchMap";
+pub const HELP_WORKSHOP_ARGUMENT: &str = "valid arguments are [import, fetch, list]";
+pub const HELP_CONFIG_ARGUMENT: &str = "valid arguments are [backups, restore, export, import, reload]";
+pub const HELP_CONFIG_SECTION: &str = "valid sections are [maps, pools, aliases, permissions, settings]";
+pub const HELP_CONFIG_IMPORT: &str = "valid arguments are [merge, replace, confirm, cancel]";
+pub const HELP_CONFIG_BACKUP: &str = "specify the number of the backup as shown by -config backups";
+pub const HELP_CATALOG_ARGUMENT: &str = "valid arguments are [modes, maps, mode, map]";
+pub const HELP_CATALOG_ENTRY: &str = "valid arguments are [add, remove]";
+pub const HELP_CATALOG_MODE_NAME: &str = "specify the game mode name as the server knows it, for example PUSH";
+pub const HELP_CATALOG_MAP_NAME: &str = "specify the map name as the server knows it, for example stalingrad";
+pub const HELP_CHANNEL_MODE: &str = "valid arguments are [lock, unlock, log, unlog]";
+pub const HELP_ALIAS_ARGUMENT: &str = "valid arguments are [add, remove, rename, show, list]";
+pub const HELP_ALIAS: &str = "valid aliases are at least 3 characters of a to z, numbers 0-9, _ and -";
 pub const HELP_ALIAS_OR_MAP: &str = "specify a valid alias or default map. (use -map list or -map default to find map names)";
 pub const HELP_VOTE_AMOUNT : &str = "valid arguments are [choices]";
 pub const HELP_VOTE_CHOICE_NUMBER: &str = "valid number of choices are [2-10]";
 pub const HELP_TEAM_CREATE: &str = "valid arguments are [teams]";
 pub const HELP_TEAM_CHANNEL: &str = "requires channel id to identify channel";
+pub const HELP_POOL_ARGUMENT: &str = "valid arguments are [list, create, delete, add, remove, use, show, schedule]";
+pub const HELP_POOL_NAME: &str = "specify a pool name (use -pool list to find pools)";
+pub const HELP_POOL_SCHEDULE: &str = "valid arguments are [add, remove, list], remove takes the number shown by -pool schedule list";
+pub const HELP_POOL_TIME: &str = "specify a UTC time as HH:MM, optionally followed by a weekday [mon, tue, wed, thu, fri, sat, sun]";
+pub const HELP_VOTEKICK: &str = "specify a player name or steam id and a reason, or one of [immune, cooldown, ban, bans]";
+pub const HELP_VOTEKICK_IMMUNE: &str = "valid arguments are [add, remove, list]";
+pub const HELP_VOTEKICK_SETTING: &str = "specify the number of minutes";
+pub const HELP_LINK: &str = "specify your steam id (64 bit) or [confirm, list]";
+pub const HELP_WHITELIST: &str = "valid arguments are [on, off, add, remove, list, grace]";
+pub const HELP_WHITELIST_GRACE: &str = "specify the number of seconds";
+pub const HELP_RULES: &str = "valid arguments are [list, add, remove, dryrun, ban], remove takes the number shown by -rules list and dryrun takes [on, off]";
+pub const HELP_RULE_TYPE: &str = "valid rule types are [name, kda, score, afk, imbalance]";
+pub const HELP_RULE_VALUE: &str = "specify a regex for name, a kills per death ratio for kda, a score for score, minutes for afk or a number of players for imbalance";
+pub const HELP_RULE_ACTION: &str = "valid actions are [warn, kick, ban, switch]";
+pub const HELP_AUTOBALANCE: &str = "valid arguments are [on, off, threshold, cooldown]";
+pub const HELP_AUTOBALANCE_VALUE: &str = "specify the number of players for threshold or minutes for cooldown";
+pub const HELP_MATCHES: &str = "valid arguments are [recent]";
+pub const HELP_MATCHES_COUNT: &str = "specify how many matches to show, at least 1";
+pub const HELP_ECONOMY: &str = "valid arguments are [list, create, delete, set, use]";
+pub const HELP_ECONOMY_NAME: &str = "specify an economy preset name (use -economy list to find presets), use takes none to clear the preset of maps";
+pub const HELP_ECONOMY_SETTING: &str = "valid payouts are [round, map, losing] followed by the cash amount, 0 turns a payout off";
+pub const HELP_ME: &str = "valid arguments are [stats]";
+pub const HELP_TEAM_SWAP: &str = "specify the discord user (mention or id) to move to the other team channel";
@@ -0,0 +1,177 @@
+use rand::seq::SliceRandom;
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument, get_player_list, inspect_all};
+use crate::pavlov::{pa, parse_number, PavlovCommands, SteamId};
+use crate::config::Items;
+use crate::help::{HELP_ITEM, HELP_ITEM_TARGET, HELP_ITEM_CATEGORY, HELP_ITEMS_ARGUMENT, HELP_VALID_TEAM};
+
+const MAX_SUGGESTIONS: usize = 3;
+const MAX_DISTANCE: usize = 3;
+
+/// Who receives the item of a `-giveitem` command.
+enum ItemTarget {
+    Player(SteamId),
+    Team(u32),
+    Everybody,
+}
+
+pub fn handle_items(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_ITEMS_ARGUMENT)?.to_lowercase().as_str() {
+        "refresh" => {
+            framework.items.clear();
+            let items = get_items(framework)?;
+            Ok(format!("Fetched {} items from the server", items.len()))
+        }
+        "categories" => {
+            let catalog = framework.config.get_catalog();
+            Ok(catalog.item_categories.iter().fold("".to_string(), |a, category| {
+                format!("{}\n{}: {}", a, category.name, category.items.join(", "))
+            }))
+        }
+        x => invalid_argument(x, HELP_ITEMS_ARGUMENT)
+    }
+}
+
+pub fn handle_give_item(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let (target, item_index) = match pa(arguments, 1, HELP_ITEM_TARGET)?.to_lowercase().as_str() {
+        "all" | "everybody" => (ItemTarget::Everybody, 2),
+        "team" => (ItemTarget::Team(parse_number(pa(arguments, 2, HELP_VALID_TEAM)?)?), 3),
+        value => (ItemTarget::Player(parse_number(value)?), 2)
+    };
+    let item = pa(arguments, item_index, HELP_ITEM)?;
+    let category = if item.eq_ignore_ascii_case("random") {
+        Some(pa(arguments, item_index + 1, HELP_ITEM_CATEGORY)?)
+    } else {
+        None
+    };
+    let players: Vec<SteamId> = match target {
+        ItemTarget::Player(steam_id) => vec![steam_id],
+        ItemTarget::Team(team) => {
+            let players = get_player_list(framework)?;
+            inspect_all(players, framework)?.iter().filter(|player| {
+                parse_number::<u32>(player.TeamId.as_str()).map(|value| value == team).unwrap_or(false)
+            }).map(|player| parse_number(player.UniqueId.as_str())).collect::<Result<Vec<SteamId>, IvanError>>()?
+        }
+        ItemTarget::Everybody => get_player_list(framework)?.iter().map(|player| {
+            parse_number(player.UniqueId.as_str())
+        }).collect::<Result<Vec<SteamId>, IvanError>>()?
+    };
+    if players.is_empty() {
+        return Ok("Could not give items because there are no (matching) players on the server".to_string());
+    }
+    let mut message = String::new();
+    for steam_id in players {
+        let item = match category {
+            Some(category) => random_item(category, framework)?,
+            None => validate_item(item, framework)?
+        };
+        let response = framework.connection.execute_command(PavlovCommands::GiveItem(steam_id, item.clone()));
+        message.push_str(format!("{} gets \"{}\": {}\n", steam_id, item, response.trim()).as_str());
+    }
+    Ok(message)
+}
+
+/// Returns the items the server reported with `ItemList`, the list is fetched once and cached.
+fn get_items(framework: &mut CustomFramework) -> Result<&Vec<String>, IvanError> {
+    if framework.items.is_empty() {
+        let response = framework.connection.execute_command(PavlovCommands::ItemList);
+        let items = serde_json::from_str::<Items>(response.as_str()).map_err(|err| {
+            IvanError { input: format!("could not parse the server item list because of {}", err), kind: BotErrorKind::InvalidItem }
+        })?;
+        framework.items = items.ItemList;
+    }
+    Ok(&framework.items)
+}
+
+fn validate_item(item: &str, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let items = match get_items(framework) {
+        Ok(items) => items,
+        Err(err) => {
+            println!("giving item without validation: {}", err);
+            return Ok(item.to_string());
+        }
+    };
+    if let Some(found) = items.iter().find(|value| value.eq_ignore_ascii_case(item)) {
+        return Ok(found.clone());
+    }
+    let suggestions = suggest(item, items);
+    Err(IvanError {
+        input: if suggestions.is_empty() {
+            format!("\"{}\" is not an item on this server, use -itemlist to see all items", item)
+        } else {
+            format!("\"{}\" is not an item on this server, did you mean: {}", item, suggestions.join(", "))
+        },
+        kind: BotErrorKind::InvalidItem,
+    })
+}
+
+fn random_item(category: &str, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let catalog = framework.config.get_catalog();
+    let entry = catalog.find_item_category(category).cloned().ok_or_else(|| {
+        IvanError { input: format!("\"{}\" {}", category, HELP_ITEM_CATEGORY), kind: BotErrorKind::InvalidItem }
+    })?;
+    let server_items = get_items(framework).ok().cloned().unwrap_or_default();
+    let available: Vec<&String> = entry.items.iter().filter(|item| {
+        server_items.is_empty() || server_items.iter().any(|value| value.eq_ignore_ascii_case(item))
+    }).collect();
+    available.choose(&mut rand::thread_rng()).map(|item| (*item).clone()).ok_or_else(|| {
+        IvanError { input: format!("none of the items in category \"{}\" exist on this server", category), kind: BotErrorKind::InvalidItem }
+    })
+}
+
+fn suggest<'a>(item: &str, items: &'a [String]) -> Vec<&'a str> {
+    let lower = item.to_lowercase();
+    if lower.is_empty() {
+        return vec![];
+    }
+    let mut scored: Vec<(usize, &String)> = items.iter().map(|value| {
+        let candidate = value.to_lowercase();
+        let score = if candidate.contains(&lower) || lower.contains(&candidate) { 0 } else { distance(&lower, &candidate) };
+        (score, value)
+    }).filter(|(score, _)| *score <= MAX_DISTANCE).collect();
+    scored.sort_by_key(|(score, _)| *score);
+    scored.iter().take(MAX_SUGGESTIONS).map(|(_, value)| value.as_str()).collect()
+}
+
+/// Levenshtein distance between two item names.
+fn distance(first: &str, second: &str) -> usize {
+    let second: Vec<char> = second.chars().collect();
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    for (i, a) in first.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in second.iter().enumerate() {
+            let cost = if a == *b { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[second.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_counts_the_edits() {
+        let cases = [("", "", 0), ("", "ak47", 4), ("ak47", "", 4), ("ak47", "ak47", 0), ("ak47", "ak74", 2), ("kitten", "sitting", 3)];
+        for (first, second, expected) in cases.iter() {
+            assert_eq!(distance(first, second), *expected, "{} -> {}", first, second);
+        }
+    }
+
+    #[test]
+    fn suggestions_are_the_closest_items() {
+        let items = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+        let cases: [(&str, Vec<String>, Vec<&str>); 5] = [
+            ("", items(&["ak47"]), vec![]),
+            ("ak47", items(&[]), vec![]),
+            ("AK", items(&["ak47", "ak12", "akm", "ak74"]), vec!["ak47", "ak12", "akm"]),
+            ("ak1x", items(&["m4a1", "ak15", "ak12"]), vec!["ak15", "ak12"]),
+            ("deagle", items(&["m4a1"]), vec![]),
+        ];
+        for (item, items, expected) in cases.iter() {
+            assert_eq!(suggest(item, items), *expected, "{}", item);
+        }
+    }
+}
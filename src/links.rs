@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::audit::audit;
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, get_player_list, inspect_player};
+use crate::pavlov::{pa, parse_number};
+use crate::permissions::PermissionLevel;
+use crate::help::{HELP_LINK, HELP_ME};
+
+/// How long a link code can be confirmed.
+const LINK_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// A discord account that proved it owns a steam account.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Link {
+    pub discord: u64,
+    pub steam: u64,
+}
+
+/// A `-link` that waits for the player to put the code in their in-game name.
+pub struct PendingLink {
+    steam: u64,
+    code: String,
+    created: Instant,
+}
+
+/// `-link steamid` hands out a code, `-link confirm` checks that the player with that steam id
+/// has the code in their in-game name. RCON can't whisper to a player so the name is the only
+/// channel the bot can read back. `-link list` shows all links to mods.
+pub fn handle_link(arguments: &Vec<&str>, framework: &mut CustomFramework, author: u64, permission: PermissionLevel) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_LINK)?.to_lowercase().as_str() {
+        "confirm" => confirm_link(framework, author),
+        "list" if permission <= PermissionLevel::Mod => {
+            Ok(framework.config.get_links().iter().fold("Linked accounts:".to_string(), |a, link| {
+                format!("{}\n<@{}> {}", a, link.discord, link.steam)
+            }))
+        }
+        value => {
+            let steam: u64 = parse_number(value)?;
+            if let Some(discord) = framework.config.find_discord_link(steam) {
+                if discord != author {
+                    return Err(IvanError { input: format!("{} is already linked to another discord account", steam), kind: BotErrorKind::InvalidArgument });
+                }
+            }
+            let code = format!("IVAN{:04}", rand::thread_rng().gen_range(0, 10000));
+            let response = format!("Add {} to your in-game name, join the server and use -link confirm within {} minutes. You can change your name back afterwards.", code, LINK_TIMEOUT.as_secs() / 60);
+            framework.pending_links.insert(author, PendingLink { steam, code, created: Instant::now() });
+            Ok(response)
+        }
+    }
+}
+
+fn confirm_link(framework: &mut CustomFramework, author: u64) -> Result<String, IvanError> {
+    let pending = framework.pending_links.remove(&author).ok_or_else(|| {
+        IvanError { input: "there is no link to confirm, start with -link steamid".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    if pending.created.elapsed() > LINK_TIMEOUT {
+        return Err(IvanError { input: "the link code expired, use -link steamid again".to_string(), kind: BotErrorKind::InvalidArgument });
+    }
+    let players = get_player_list(framework)?;
+    let player = players.iter().find(|player| player.UniqueId == pending.steam.to_string());
+    match player {
+        Some(player) if player.Username.to_lowercase().contains(&pending.code.to_lowercase()) => {
+            framework.config.add_link(Link { discord: author, steam: pending.steam })?;
+            audit(author, format!("linked steam account {}", pending.steam));
+            Ok(format!("Linked your discord account to {} ({})", player.Username, pending.steam))
+        }
+        Some(player) => {
+            let response = format!("{} doesn't have {} in their name yet, try -link confirm again", player.Username, pending.code);
+            framework.pending_links.insert(author, pending);
+            Ok(response)
+        }
+        None => {
+            let response = format!("{} is not on the server, join with {} in your name and try -link confirm again", pending.steam, pending.code);
+            framework.pending_links.insert(author, pending);
+            Ok(response)
+        }
+    }
+}
+
+pub fn handle_unlink(framework: &mut CustomFramework, author: u64) -> Result<String, IvanError> {
+    let steam = framework.config.find_steam_link(author).ok_or_else(|| {
+        IvanError { input: "your discord account is not linked".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    framework.config.remove_link(author)?;
+    audit(author, format!("unlinked steam account {}", steam));
+    Ok(format!("Unlinked steam account {}", steam))
+}
+
+/// `-me` shows the linked steam account, `-me stats` the current stats of that player.
+pub fn handle_me(arguments: &Vec<&str>, framework: &mut CustomFramework, author: u64) -> Result<String, IvanError> {
+    let steam = framework.config.find_steam_link(author).ok_or_else(|| {
+        IvanError { input: "your discord account is not linked, use -link steamid".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    match arguments.get(1).map(|value| value.to_lowercase()) {
+        None => Ok(format!("Your discord account is linked to steam account {}", steam)),
+        Some(value) if value == "stats" => {
+            let players = get_player_list(framework)?;
+            let player = match players.iter().find(|player| player.UniqueId == steam.to_string()) {
+                Some(player) => player.clone(),
+                None => return Ok(format!("{} is not on the server right now", steam))
+            };
+            let info = inspect_player(&player, framework)?;
+            Ok(format!("{}\nKDA: {}\nScore: {}\nCash: {}\nTeam: {}", info.PlayerName, info.KDA, info.Score, info.Cash, info.TeamId))
+        }
+        Some(value) => Err(IvanError { input: format!("\"{}\" {}", value, HELP_ME), kind: BotErrorKind::InvalidArgument })
+    }
+}
@@ -11,6 +11,26 @@ mod connect;
 mod pavlov;
 mod credentials;
 mod help;
+mod audit;
+mod catalog;
+mod items;
+mod workshop;
+mod migration;
+mod backup;
+mod config_commands;
+mod transfer;
+mod background;
+mod storage;
+mod pools;
+mod votekick;
+mod links;
+mod whitelist;
+mod rules;
+mod autobalance;
+mod matches;
+mod economy;
+mod mapactions;
+mod skins;
 
 fn main() {
     run_discord();
@@ -0,0 +1,192 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::background::{announce, PlayerSnapshot};
+use crate::config::{IvanConfig, ServerInfo, Player};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument};
+use crate::pavlov::{PavlovCommands, Skin, pa, parse_number, parse_skin, parse_game_mode};
+use crate::help::{HELP_MAP_ACTION, HELP_MAP_ACTION_TYPE, HELP_ALIAS_OR_MAP, HELP_LIMITED_AMMO};
+
+/// Something done once a pool map is running on the server.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MapAction {
+    /// A skin for everyone, or random.
+    Skin { skin: String },
+    Ammo { ammo: String },
+    /// Everyone's cash is set to this.
+    Cash { cash: u32 },
+    /// Posted in the log channel.
+    Announce { message: String },
+}
+
+impl fmt::Display for MapAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapAction::Skin { skin } => write!(f, "skin {}", skin),
+            MapAction::Ammo { ammo } => write!(f, "limited ammo type {}", ammo),
+            MapAction::Cash { cash } => write!(f, "cash {}", cash),
+            MapAction::Announce { message } => write!(f, "announce \"{}\"", message)
+        }
+    }
+}
+
+/// The map the actions wait for, kept by the background thread.
+#[derive(Default)]
+pub struct MapActionState {
+    pending: Option<ServerInfo>,
+}
+
+/// `-map action add {alias/map} (gamemode) [skin, ammo, cash, announce] value`,
+/// `-map action remove {alias/map} (gamemode) X` and `-map action list {alias/map} (gamemode)`.
+pub fn handle_map_action(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    let command = pa(arguments, 2, HELP_MAP_ACTION)?.to_lowercase();
+    let alias_or_map = pa(arguments, 3, HELP_ALIAS_OR_MAP)?;
+    // the game mode is optional, only a map in the pool with several modes needs it
+    let gamemode = arguments.get(4).and_then(|value| parse_game_mode(value, config).ok());
+    let offset = if gamemode.is_some() { 1 } else { 0 };
+    let map = config.find_single_pool_map(alias_or_map, gamemode.as_ref())?;
+    let name = format!("{} ({})", map.alias, map.gamemode);
+    let mut actions = map.actions.clone();
+    match command.as_str() {
+        "add" => {
+            let value = pa(arguments, 5 + offset, HELP_MAP_ACTION_TYPE)?;
+            let action = match pa(arguments, 4 + offset, HELP_MAP_ACTION_TYPE)?.to_lowercase().as_str() {
+                "skin" => {
+                    if !value.eq_ignore_ascii_case("random") {
+                        parse_skin(value)?;
+                    }
+                    MapAction::Skin { skin: value.to_lowercase() }
+                }
+                "ammo" => {
+                    if !["0", "1", "2"].contains(&value) {
+                        return invalid_argument(value, HELP_LIMITED_AMMO);
+                    }
+                    MapAction::Ammo { ammo: value.to_string() }
+                }
+                "cash" => MapAction::Cash { cash: parse_number(value)? },
+                "announce" => MapAction::Announce { message: arguments.iter().skip(5 + offset).cloned().collect::<Vec<&str>>().join(" ") },
+                x => return invalid_argument(x, HELP_MAP_ACTION_TYPE)
+            };
+            let response = format!("Added \"{}\" to the actions of {}", action, name);
+            actions.push(action);
+            config.set_map_actions(alias_or_map, gamemode.as_ref(), actions)?;
+            Ok(response)
+        }
+        "remove" => {
+            let index: usize = parse_number(pa(arguments, 4 + offset, HELP_MAP_ACTION)?)?;
+            if index == 0 || index > actions.len() {
+                return Err(IvanError { input: format!("{} has no action {}, use -map action list {}", name, index, arguments[3..4 + offset].join(" ")), kind: BotErrorKind::InvalidArgument });
+            }
+            let action = actions.remove(index - 1);
+            config.set_map_actions(alias_or_map, gamemode.as_ref(), actions)?;
+            Ok(format!("Removed \"{}\" from the actions of {}", action, name))
+        }
+        "list" => {
+            if actions.is_empty() {
+                return Ok(format!("{} has no actions", name));
+            }
+            Ok(actions.iter().enumerate().fold(format!("Actions of {} once it runs:", name), |a, (index, action)| {
+                format!("{}\n{}: {}", a, index + 1, action)
+            }))
+        }
+        x => invalid_argument(x, HELP_MAP_ACTION)
+    }
+}
+
+/// Called when the server runs a new map, the actions follow once its round has started.
+pub fn map_actions_map_changed(state: &mut MapActionState, info: ServerInfo) {
+    state.pending = Some(info);
+}
+
+/// Whether the actions of the `pending` map can run: the server still runs it, its round has
+/// started and players are spawned. `None` when the map is gone and the actions are dropped.
+fn is_ready(pending: &ServerInfo, info: &ServerInfo, players: &[Player]) -> Option<bool> {
+    if !info.MapLabel.eq_ignore_ascii_case(&pending.MapLabel) {
+        return None;
+    }
+    Some(info.RoundState.eq_ignore_ascii_case("Started") && !players.is_empty())
+}
+
+/// Runs the actions of the new map once the last ServerInfo of the match tracker reports its
+/// round as started while players are online.
+pub fn run_map_actions(framework: &mut CustomFramework, http: &Http, state: &mut MapActionState, info: Option<&ServerInfo>, snapshot: &mut PlayerSnapshot) {
+    let (pending, info) = match (&state.pending, info) {
+        (Some(pending), Some(info)) => (pending, info),
+        _ => return
+    };
+    let players = match snapshot.players(framework) {
+        Ok(players) => players,
+        Err(err) => {
+            println!("could not get the players for the map actions: {}", err);
+            return;
+        }
+    };
+    match is_ready(pending, info, &players) {
+        Some(true) => {}
+        Some(false) => return,
+        None => {
+            state.pending = None;
+            return;
+        }
+    }
+    let actions = match state.pending.take().and_then(|pending| framework.config.find_running_map(&pending.MapLabel, &pending.GameMode).map(|map| map.actions.clone())) {
+        Some(actions) => actions,
+        None => return
+    };
+    for action in actions {
+        run_action(framework, http, &players, &action).unwrap_or_else(|err| {
+            println!("could not run the map action \"{}\": {}", action, err);
+        });
+    }
+}
+
+fn run_action(framework: &mut CustomFramework, http: &Http, players: &[Player], action: &MapAction) -> Result<(), IvanError> {
+    match action {
+        MapAction::Skin { skin } => {
+            let fixed = if skin == "random" { None } else { Some(parse_skin(skin)?) };
+            for player in players {
+                let skin = fixed.unwrap_or_else(Skin::get_random);
+                framework.connection.execute_command(PavlovCommands::SetPlayerSkin(parse_number(player.UniqueId.as_str())?, skin));
+            }
+        }
+        MapAction::Ammo { ammo } => {
+            framework.connection.execute_command(PavlovCommands::SetLimitedAmmoType(ammo.clone()));
+        }
+        MapAction::Cash { cash } => {
+            for player in players {
+                framework.connection.execute_command(PavlovCommands::SetCash(parse_number(player.UniqueId.as_str())?, *cash));
+            }
+        }
+        MapAction::Announce { message } => announce(http, &framework.config, message.clone())
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matches::tests::server_info;
+
+    fn info(map: &str, state: &str) -> ServerInfo {
+        server_info(map, state, "0", "0")
+    }
+
+    fn player() -> Player {
+        Player { Username: "ivan".to_string(), UniqueId: "76561198000000000".to_string() }
+    }
+
+    #[test]
+    fn actions_wait_for_a_started_round_with_players() {
+        let pending = info("datacenter", "Starting");
+        assert_eq!(is_ready(&pending, &info("datacenter", "Starting"), &[player()]), Some(false));
+        assert_eq!(is_ready(&pending, &info("datacenter", "Started"), &[]), Some(false));
+        assert_eq!(is_ready(&pending, &info("DataCenter", "Started"), &[player()]), Some(true));
+    }
+
+    #[test]
+    fn actions_of_a_map_that_is_gone_are_dropped() {
+        assert_eq!(is_ready(&info("datacenter", "Started"), &info("santorini", "Started"), &[player()]), None);
+    }
+}
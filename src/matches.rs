@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::backup::format_age;
+use crate::background::{announce, PlayerSnapshot};
+use crate::config::{IvanConfig, ServerInfo};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, invalid_argument, get_server_info};
+use crate::pavlov::{TeamId, pa, parse_number};
+use crate::storage::get_storage;
+use crate::votekick::now;
+use crate::help::{HELP_MATCHES, HELP_MATCHES_COUNT};
+
+const RECENT_MATCHES: usize = 5;
+
+/// A match from map load to the next map, stored when it ends.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MatchRecord {
+    pub start: u64,
+    pub end: u64,
+    pub map: String,
+    pub gamemode: String,
+    pub red_score: String,
+    pub blue_score: String,
+    pub players: Vec<MatchPlayer>,
+}
+
+/// The last stats seen of a player that took part in a match.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MatchPlayer {
+    pub steam_id: u64,
+    pub name: String,
+    pub team: TeamId,
+    pub kda: String,
+    pub score: String,
+}
+
+/// What changed on the server since the previous poll.
+#[derive(Clone, PartialEq)]
+pub enum MatchEvent {
+    MapChanged(String),
+    RoundStarted,
+    RoundEnded,
+    MatchEnded,
+}
+
+/// The match in progress, kept by the background thread.
+#[derive(Default)]
+pub struct MatchTracker {
+    info: Option<ServerInfo>,
+    current: Option<MatchRecord>,
+}
+
+impl MatchTracker {
+    /// The server info of the last poll.
+    pub fn last_info(&self) -> Option<&ServerInfo> {
+        self.info.as_ref()
+    }
+}
+
+/// `-matches recent (X)` lists the last X (default 5) finished matches.
+pub fn handle_matches(arguments: &Vec<&str>, config: &IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_MATCHES)?.to_lowercase().as_str() {
+        "recent" => {
+            let count = match arguments.get(2) {
+                Some(value) => parse_number(value)?,
+                None => RECENT_MATCHES
+            };
+            if count == 0 {
+                return invalid_argument("0", HELP_MATCHES_COUNT);
+            }
+            let matches = get_storage().recent_matches(count)?;
+            if matches.is_empty() {
+                return Ok("No matches were recorded yet".to_string());
+            }
+            Ok(matches.iter().fold("Recent matches:".to_string(), |a, record| {
+                format!("{}\n{}, ended {}", a, summarize(config, record), format_age(record.end))
+            }))
+        }
+        x => invalid_argument(x, HELP_MATCHES)
+    }
+}
+
+/// Polls ServerInfo, records the players of the running match and stores the match once the map
+/// changes. The first poll after a start only picks up the running match without events.
+pub fn track_match(framework: &mut CustomFramework, http: &Http, tracker: &mut MatchTracker, snapshot: &mut PlayerSnapshot) -> Vec<MatchEvent> {
+    let info = match get_server_info(framework) {
+        Ok(info) => info,
+        Err(err) => {
+            println!("could not track the match: {}", err);
+            return vec![];
+        }
+    };
+    let mut events = vec![];
+    match tracker.info.take() {
+        Some(previous) if previous.MapLabel == info.MapLabel => events.extend(round_events(&previous, &info)),
+        previous => {
+            if let Some(record) = tracker.current.take() {
+                finish_match(framework, http, record);
+                events.push(MatchEvent::MatchEnded);
+            }
+            if previous.is_some() {
+                events.push(MatchEvent::MapChanged(info.MapLabel.clone()));
+                // the first round can already be running when the new map is noticed
+                if info.RoundState.eq_ignore_ascii_case("Started") {
+                    events.push(MatchEvent::RoundStarted);
+                }
+            }
+            tracker.current = Some(MatchRecord {
+                start: now(), end: 0, map: info.MapLabel.clone(), gamemode: info.GameMode.clone(),
+                red_score: String::new(), blue_score: String::new(), players: vec![],
+            });
+        }
+    }
+    if let Some(record) = tracker.current.as_mut() {
+        record.red_score = info.Team1Score.clone();
+        record.blue_score = info.Team0Score.clone();
+        match snapshot.inspected(framework) {
+            Ok(players) => for player in players {
+                let steam_id: u64 = match player.UniqueId.parse() {
+                    Ok(value) => value,
+                    Err(_) => continue
+                };
+                let seen = MatchPlayer { steam_id, name: player.PlayerName, team: player.TeamId.trim().parse().unwrap_or(0), kda: player.KDA, score: player.Score };
+                record.players.retain(|value| value.steam_id != steam_id);
+                record.players.push(seen);
+            }
+            Err(err) => println!("could not record the players of the match: {}", err)
+        }
+    }
+    tracker.info = Some(info);
+    events
+}
+
+/// The round events between two polls of the same map. A round can end and the next one start
+/// between two polls, the state is then the same but the total score went up.
+fn round_events(previous: &ServerInfo, info: &ServerInfo) -> Vec<MatchEvent> {
+    let started = info.RoundState.eq_ignore_ascii_case("Started");
+    if previous.RoundState != info.RoundState {
+        if started {
+            return vec![MatchEvent::RoundStarted];
+        } else if previous.RoundState.eq_ignore_ascii_case("Started") {
+            return vec![MatchEvent::RoundEnded];
+        }
+    } else if started && total_score(previous).is_some() && total_score(previous) != total_score(info) {
+        return vec![MatchEvent::RoundEnded, MatchEvent::RoundStarted];
+    }
+    vec![]
+}
+
+fn total_score(info: &ServerInfo) -> Option<i64> {
+    Some(info.Team0Score.trim().parse::<i64>().ok()? + info.Team1Score.trim().parse::<i64>().ok()?)
+}
+
+fn finish_match(framework: &mut CustomFramework, http: &Http, mut record: MatchRecord) {
+    record.end = now();
+    if let Err(err) = get_storage().add_match(&record) {
+        println!("could not store the match on {}: {}", record.map, err);
+    }
+    let mut summary = format!("Match over: {}", summarize(&framework.config, &record));
+    for (team, label) in &[(1, "Red"), (0, "Blue")] {
+        let mut players: Vec<&MatchPlayer> = record.players.iter().filter(|player| player.team == *team).collect();
+        players.sort_by_key(|player| std::cmp::Reverse(player.score.trim().parse::<i64>().unwrap_or(0)));
+        summary = players.iter().fold(format!("{}\n{}:", summary, label), |a, player| {
+            format!("{}\n  {} \tscore {} \tKDA {}", a, player.name, player.score, player.kda)
+        });
+    }
+    announce(http, &framework.config, summary);
+}
+
+fn summarize(config: &IvanConfig, record: &MatchRecord) -> String {
+    let map = config.find_pool_map(&record.map).map(|map| map.alias.clone()).unwrap_or_else(|| record.map.clone());
+    format!("{} ({}) red {} - {} blue, {} minutes, {} players", map, record.gamemode, record.red_score, record.blue_score,
+            record.end.saturating_sub(record.start) / 60, record.players.len())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn server_info(map: &str, state: &str, blue: &str, red: &str) -> ServerInfo {
+        ServerInfo { MapLabel: map.to_string(), GameMode: "SND".to_string(), RoundState: state.to_string(), Team0Score: blue.to_string(), Team1Score: red.to_string() }
+    }
+
+    #[test]
+    fn round_state_changes_start_and_end_rounds() {
+        assert!(round_events(&server_info("datacenter", "Starting", "0", "0"), &server_info("datacenter", "Started", "0", "0")) == vec![MatchEvent::RoundStarted]);
+        assert!(round_events(&server_info("datacenter", "Started", "0", "0"), &server_info("datacenter", "Ended", "1", "0")) == vec![MatchEvent::RoundEnded]);
+        assert!(round_events(&server_info("datacenter", "Started", "1", "0"), &server_info("datacenter", "Started", "1", "0")).is_empty());
+    }
+
+    #[test]
+    fn a_round_between_two_polls_is_noticed_by_the_score() {
+        let events = round_events(&server_info("datacenter", "Started", "1", "0"), &server_info("datacenter", "Started", "1", "1"));
+        assert!(events == vec![MatchEvent::RoundEnded, MatchEvent::RoundStarted]);
+    }
+
+    #[test]
+    fn summaries_show_score_length_and_players() {
+        let player = MatchPlayer { steam_id: 1, name: "Ivan".to_string(), team: 1, kda: "3/1/0".to_string(), score: "300".to_string() };
+        let record = MatchRecord {
+            start: 1000, end: 1000 + 25 * 60, map: "datacenter".to_string(), gamemode: "SND".to_string(),
+            red_score: "7".to_string(), blue_score: "5".to_string(), players: vec![player],
+        };
+        assert_eq!(summarize(&IvanConfig::default(), &record), "datacenter (SND) red 7 - 5 blue, 25 minutes, 1 players");
+    }
+}
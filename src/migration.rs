@@ -0,0 +1,182 @@
+use serde_json::{Value, Map, json};
+use crate::model::{IvanError, BotErrorKind};
+
+/// Version written to new and migrated config files.
+pub const CONFIG_VERSION: u8 = 4;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), IvanError>;
+
+/// `MIGRATIONS[i]` upgrades a config from version `i + 1` to version `i + 2`.
+const MIGRATIONS: [Migration; 3] = [
+    migrate_1_to_2,
+    migrate_2_to_3,
+    migrate_3_to_4,
+];
+
+/// Upgrades a parsed config file step by step to `CONFIG_VERSION`. Returns the upgraded value and
+/// the version the file had when it had to be migrated.
+pub fn migrate(mut value: Value) -> Result<(Value, Option<u8>), IvanError> {
+    let object = value.as_object_mut().ok_or_else(|| {
+        IvanError { input: "the config file is not a json object".to_string(), kind: BotErrorKind::MigrationError }
+    })?;
+    // configs from before the version field was read are treated as the first version
+    let original = object.get("version").and_then(|version| version.as_u64()).unwrap_or(1);
+    if original < 1 {
+        return Err(IvanError {
+            input: format!("the config file has version {} but versions start at 1", original),
+            kind: BotErrorKind::MigrationError,
+        });
+    }
+    if original > CONFIG_VERSION as u64 {
+        return Err(IvanError {
+            input: format!("the config file has version {} but this bot only understands up to version {}", original, CONFIG_VERSION),
+            kind: BotErrorKind::MigrationError,
+        });
+    }
+    if original == CONFIG_VERSION as u64 {
+        return Ok((value, None));
+    }
+    for version in original..CONFIG_VERSION as u64 {
+        let migration = MIGRATIONS[(version - 1) as usize];
+        migration(object).map_err(|err| {
+            IvanError { input: format!("migrating from version {} failed: {}", version, err.input), kind: BotErrorKind::MigrationError }
+        })?;
+        object.insert("version".to_string(), json!(version + 1));
+        println!("migrated config from version {} to {}", version, version + 1);
+    }
+    Ok((value, Some(original as u8)))
+}
+
+fn insert_missing(object: &mut Map<String, Value>, key: &str, value: Value) {
+    if !object.contains_key(key) {
+        object.insert(key.to_string(), value);
+    }
+}
+
+/// Version 2 added the skin shuffle and gun mode settings.
+fn migrate_1_to_2(object: &mut Map<String, Value>) -> Result<(), IvanError> {
+    for key in ["admins", "mods", "aliases", "maps"].iter() {
+        insert_missing(object, key, json!([]));
+    }
+    insert_missing(object, "channel_lock", Value::Null);
+    insert_missing(object, "skin_shuffle", json!(false));
+    insert_missing(object, "gun_mode", json!("Modern"));
+    Ok(())
+}
+
+/// Version 3 added the vote size and the team voice channels.
+fn migrate_2_to_3(object: &mut Map<String, Value>) -> Result<(), IvanError> {
+    insert_missing(object, "map_vote_count", json!(8));
+    insert_missing(object, "team_channels", Value::Null);
+    Ok(())
+}
+
+/// Version 4 made aliases unique regardless of case. Lookups always returned the first alias, so
+/// that one is kept. Pool maps take the spelling of the kept alias, a pool map of another map
+/// under the same alias gets a new alias so every alias finds one map.
+fn migrate_3_to_4(object: &mut Map<String, Value>) -> Result<(), IvanError> {
+    let aliases = object.get("aliases").and_then(|aliases| aliases.as_array()).cloned().unwrap_or_default();
+    // the alias as it is kept and its map
+    let mut kept: Vec<(String, String)> = vec![];
+    for alias in aliases {
+        let key = alias.get(0).and_then(|key| key.as_str()).unwrap_or("").to_string();
+        let map = alias.get(1).and_then(|map| map.as_str()).unwrap_or("").to_string();
+        if kept.iter().any(|(other, _)| other.eq_ignore_ascii_case(&key)) {
+            println!("removed duplicate alias {}", alias);
+            continue;
+        }
+        kept.push((key, map));
+    }
+    // pool maps can use an alias that is not in the alias list
+    let mut unlisted: Vec<(String, String)> = vec![];
+    let mut maps = object.get("maps").and_then(|maps| maps.as_array()).cloned().unwrap_or_default();
+    for pool_map in maps.iter_mut().filter_map(|pool_map| pool_map.as_object_mut()) {
+        let alias = pool_map.get("alias").and_then(|alias| alias.as_str()).unwrap_or("").to_string();
+        let map = pool_map.get("map").and_then(|map| map.as_str()).unwrap_or("").to_string();
+        let new_alias = match kept.iter().chain(unlisted.iter()).find(|(key, _)| key.eq_ignore_ascii_case(&alias)) {
+            Some((key, existing)) if *existing == map => key.clone(),
+            Some(_) => {
+                let new_alias = (2..).map(|number| format!("{}_{}", alias, number)).find(|candidate| {
+                    !kept.iter().chain(unlisted.iter()).any(|(key, _)| key.eq_ignore_ascii_case(candidate))
+                }).unwrap_or_default();
+                println!("renamed the alias of pool map {} from {} to {}", map, alias, new_alias);
+                kept.push((new_alias.clone(), map));
+                new_alias
+            }
+            None => {
+                unlisted.push((alias, map));
+                continue;
+            }
+        };
+        pool_map.insert("alias".to_string(), Value::from(new_alias));
+    }
+    let unique: Vec<Value> = kept.into_iter().map(|(key, map)| json!([key, map])).collect();
+    object.insert("aliases".to_string(), Value::Array(unique));
+    object.insert("maps".to_string(), Value::Array(maps));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_0_is_refused() {
+        let result = migrate(json!({"version": 0}));
+        assert!(matches!(result, Err(IvanError { kind: BotErrorKind::MigrationError, .. })));
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let result = migrate(json!({"version": CONFIG_VERSION + 1}));
+        assert!(matches!(result, Err(IvanError { kind: BotErrorKind::MigrationError, .. })));
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let config = json!({"version": CONFIG_VERSION, "aliases": []});
+        let (value, original) = migrate(config.clone()).unwrap();
+        assert_eq!(value, config);
+        assert_eq!(original, None);
+    }
+
+    #[test]
+    fn version_1_is_migrated_to_the_current_version() {
+        let config = json!({
+            "version": 1,
+            "admins": [1],
+            "aliases": [["dust", "SVR_Dust"], ["DUST", "UGC123"], ["sand", "sand"]]
+        });
+        let (value, original) = migrate(config).unwrap();
+        assert_eq!(original, Some(1));
+        assert_eq!(value["version"], json!(CONFIG_VERSION));
+        assert_eq!(value["admins"], json!([1]));
+        assert_eq!(value["mods"], json!([]));
+        assert_eq!(value["skin_shuffle"], json!(false));
+        assert_eq!(value["map_vote_count"], json!(8));
+        assert_eq!(value["aliases"], json!([["dust", "SVR_Dust"], ["sand", "sand"]]));
+    }
+
+    #[test]
+    fn pool_maps_of_a_removed_duplicate_alias_get_their_own_alias() {
+        let config = json!({
+            "version": 3,
+            "aliases": [["dust", "SVR_Dust"], ["DUST", "UGC123"]],
+            "maps": [
+                {"map": "SVR_Dust", "gamemode": "SND", "alias": "Dust"},
+                {"map": "UGC123", "gamemode": "TDM", "alias": "DUST"}
+            ]
+        });
+        let (value, _) = migrate(config).unwrap();
+        assert_eq!(value["aliases"], json!([["dust", "SVR_Dust"], ["DUST_2", "UGC123"]]));
+        assert_eq!(value["maps"][0]["alias"], json!("dust"));
+        assert_eq!(value["maps"][1]["alias"], json!("DUST_2"));
+    }
+
+    #[test]
+    fn missing_version_counts_as_version_1() {
+        let (value, original) = migrate(json!({})).unwrap();
+        assert_eq!(original, Some(1));
+        assert_eq!(value["version"], json!(CONFIG_VERSION));
+    }
+}
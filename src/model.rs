@@ -1,39 +1,134 @@
 use serenity::client::Context;
 use serenity::model::channel::{Message, ChannelType};
-use crate::pavlov::{PavlovCommands, parse_map, parse_game_mode, parse_number, Skin, DEFAULT_MAPS, pa, GameMode};
+use crate::pavlov::{PavlovCommands, parse_map, parse_game_mode, parse_number, Skin, pa, GameMode};
+use crate::catalog::handle_catalog;
+use crate::pools::handle_pool;
+use crate::whitelist::handle_whitelist;
+use crate::rules::handle_rules;
+use crate::autobalance::handle_autobalance;
+use crate::matches::handle_matches;
+use crate::economy::handle_economy;
+use crate::mapactions::handle_map_action;
+use crate::skins::{handle_skin_theme, handle_skin_team};
+use crate::votekick::handle_votekick;
+use crate::links::{handle_link, handle_unlink, handle_me};
+use crate::items::{handle_give_item, handle_items};
+use crate::workshop::handle_workshop;
+use crate::config_commands::handle_config;
 use regex::Regex;
 use crate::permissions::{handle_admin, handle_mod, PermissionLevel, mod_allowed, user_allowed};
 use crate::discord::{CustomFramework, ConcurrentFramework};
 use crate::output::output;
-use crate::config::{IvanConfig, Players, GunMode, Player, PlayerInfo, PlayerInfoContainer};
+use crate::config::{IvanConfig, PoolMap, Players, GunMode, Player, PlayerInfo, PlayerInfoContainer, ServerInfo, ServerInfoContainer, RawFilterMode};
 use crate::model::BotErrorKind::InvalidMapAlias;
-use crate::voting::{handle_vote_start};
+use crate::voting::{handle_vote_start, handle_mode_vote_start, move_to_channel, get_channel, get_voice_channel, channel_members};
+use crate::parsing::parse_discord_id;
+use serenity::model::channel::GuildChannel;
+use serenity::prelude::RwLock;
+use std::sync::Arc;
 use std::ops::{Add};
 use serenity::http::{CacheHttp, Http};
 use serenity::static_assertions::_core::fmt::Formatter;
 use core::fmt;
 use crate::pavlov::PavlovCommands::{SetPlayerSkin, SwitchTeam};
 use rand::seq::SliceRandom;
-use crate::help::{HELP_GUNMODE, HELP_SKIN_TEAM, HELP_SKIN_MODE, HELP_CHANNEL_MODE, HELP_MAP, HELP_MAP_ARGUMENT, HELP_ALIAS_ARGUMENT, HELP_ALIAS, HELP_GAMEMODE, HELP_ALIAS_OR_MAP, HELP_VOTE_AMOUNT, HELP_VOTE_CHOICE_NUMBER, HELP_TEAM_CREATE, HELP_TEAM_MODES, HELP_TEAM_CHANNEL};
+use crate::help::{HELP_GUNMODE, HELP_SKIN_TEAM, HELP_SKIN_MODE, HELP_CHANNEL_MODE, HELP_MAP, HELP_MAP_ARGUMENT, HELP_ALIAS_ARGUMENT, HELP_ALIAS, HELP_ALIAS_OR_MAP, HELP_VOTE_AMOUNT, HELP_VOTE_CHOICE_NUMBER, HELP_TEAM_CREATE, HELP_TEAM_MODES, HELP_TEAM_CHANNEL, HELP_RAW_FILTER, HELP_RAW_VERB, HELP_MAP_EDIT, HELP_MAP_BULK, HELP_TEAM_SWAP};
+use crate::audit::audit;
 use std::fmt::Display;
+use std::cmp::max;
+
+const MAP_PAGE_SIZE: usize = 15;
 
 const BOT_HELP: &str =
     "
 -admin [add,remove] discord_id_64 #Add/remove admin users
 -mod [add,remove] discord_id_64 #Add/remove moderator users
--alias [add,remove] {url/map} alias #Create a map alias
+-alias add {url/map} alias #Create a map alias
+-alias remove alias (confirm) #Remove an alias, confirm is needed when pool maps use it
+-alias rename alias new_alias #Rename an alias and the pool maps that use it
+-alias show alias #Show the map of an alias and the pool maps that use it
 -alias list #Show all aliases
 -bothelp #Help command
 -mod [add,remove] discord_id_64 #Add moderator
 -map add {url/map} gamemode alias #Add map to pool
+-map add gamemode {url/map} alias {url/map} alias ... #Add several maps with the same gamemode to the pool
+-map edit {alias/map} gamemode=X alias=Y #Change the gamemode and/or alias of a pool map
+-map [enable,disable] {alias/map} #Put a pool map back in votes or leave it out without removing it
+-map info {alias/map} #Show a pool map with its workshop details
+-map action add {alias/map} (gamemode) [skin,ammo,cash,announce] value #Run an action once the map is running: a skin (or random) for everyone, SetLimitedAmmoType, everyone's cash or a message
+-map action [list,remove] {alias/map} (gamemode) (X) #List or remove the actions of a pool map
 -map vote start (X) #Start map vote with X (optional) choices, default 3
 -map vote stop #Conclude the map vote and switch map
--map list #List the map pool
+-map vote modes #Vote on the game mode first, a map vote for the winning mode follows
+-map list (gamemode) (page) #List the map pool, optionally only one gamemode
 -map default #List default maps
+-pool list #List the named pools
+-pool [create,delete,show] name #Create, delete or show a named pool
+-pool [add,remove] name alias (alias ...) #Add or remove pool maps from a named pool
+-pool use {name/all} #Only use the maps of a named pool in votes, all uses every pool map
+-pool schedule add HH:MM (weekday) name #Switch to a named pool at a UTC time, every day or on one weekday
+-pool schedule [list,remove X] #List or remove pool schedules
+-votekick {player/steamid} reason #Start a vote to temporarily ban a player, anyone can start one
+-votekick immune [add,remove,list] steamid #Players that can't be vote kicked, besides admins and mods that used -link
+-votekick [cooldown,ban] minutes #Minutes between kick votes of one user and the length of the ban
+-votekick bans #List the temporary bans from kick votes
+-whitelist [on,off] #Private match mode, players that are not on the whitelist are kicked, admins and mods only once they used -link
+-whitelist [add,remove] steamid #Add or remove a player from the whitelist
+-whitelist list #Show the whitelist and whether it is on
+-whitelist grace seconds #How long unlisted players may stay before they are kicked, default 60
+-rules list #List the moderation rules and whether they only report (dry run)
+-rules add {name,kda,score,afk,imbalance} value {warn,kick,ban,switch} #Add a rule, for example -rules add afk 5 kick
+-rules remove X #Remove rule X from -rules list
+-rules dryrun [on,off] #Only report what the rules would do, on by default
+-rules ban minutes #How long a ban rule bans, default 60
+-economy list #List the economy presets and the maps that use them
+-economy [create,delete] name #Create or delete an economy preset
+-economy set name [round,map,losing] cash #Cash for both teams at round start, everyone's cash at the first round of a map, or a bonus for the team that is behind
+-economy use {name/none} alias (alias ...) #Use an economy preset while these pool maps run
+-matches recent (X) #Show the last X (default 5) matches with map, score and length
+-link steamid #Link your discord account to your steam account, the bot asks you to put a code in your in-game name
+-link confirm #Finish linking once the code is in your in-game name
+-link list #List the linked accounts (mods)
+-unlink #Remove the link of your discord account
+-me (stats) #Show your linked steam account or its current stats
 -team {shuffle, balance} #Will shuffle or balance the teams always creating evenly matched teams.
+-team channels channel_id channel_id #Set the red and blue team voice channels
+-team lobby channel_id #Set the voice channel -team merge moves everyone to
+-team split #Randomly split your voice channel over the team channels
+-team merge #Move everyone in the team channels to the lobby channel
+-team swap @user #Move a user to the other team channel
+-team autobalance [on,off] #Move the latest players of the bigger team when the teams are uneven, off by default
+-team autobalance threshold X #Balance when the teams differ by more than X players, default 2
+-team autobalance cooldown minutes #Minutes between two automatic balances, default 5
 -gunmode {modern,ww2,random}
--skin {random, clown, prisoner, naked, farmer, russian, nato, german, soviet, us} #Change all current players to either a random skin or a specific skin
--skin shuffle {true/false} #When enabled will execute \"skin random\" 90 seconds after a vote is completed
+-skin {random, clown, prisoner, naked, farmer, russian, nato, german, soviet, us, cop} #Change all current players to either a random skin or a specific skin
+-skin shuffle {true/false} #When enabled will execute \"skin random\" once the first round of the voted map has started
+-skin team {0,1} {skin/random} #Change the skin of one team, 0 is blue and 1 is red
+-skin theme name #Give each team the skin of a theme, for example ww2 (german vs soviet), modern, coldwar or heist
+-skin theme [list,remove name] #List the skin themes or remove a stored theme
+-skin theme add name skin skin #Store a theme with the skin of team 0 and team 1
+-skin theme mode gamemode {name/none} #Apply a theme once the first round has started after votes on this game mode, instead of the skin shuffle
+-giveitem {steam_id, team 0/1, all} {item, random category} #Give an item, items are checked against the server item list
+-items refresh #Fetch the item list from the server again
+-items categories #Show the item categories for \"giveitem all random pistol\"
+-workshop import #Import workshop metadata (titles, previews) from an attached GetPublishedFileDetails dump
+-workshop fetch #Fetch workshop metadata for the pool maps with WORKSHOP_DUMP or WORKSHOP_FETCH_COMMAND
+-workshop list #Show the stored workshop metadata
+-catalog [modes,maps] #List the game modes and stock maps the bot knows
+-catalog mode [add,remove] NAME (teams) (description) #Add/remove a game mode, add teams when it's a team mode
+-catalog map [add,remove] name #Add/remove a stock map
+-channel [lock,unlock] #Only listen to commands in this channel
+-channel [log,unlog] #Post messages the bot sends on its own (reloads, warnings) in this channel
+-config reload #Load ivan.json again after editing it by hand (this also happens automatically)
+-config backups #List the config backups, one is made before every change
+-config restore X #Restore backup X from the backup list
+-config export (maps,pools,aliases,permissions,settings) #Upload the selected sections (default all) as a json file
+-config import [merge,replace] (sections) #Dry run of importing the attached json file
+-config import [confirm,cancel] #Apply or forget the import from the dry run
+-raw command #Send any command to the server (admin only, checked against the raw filter)
+-rawfilter [allow,deny] #Only allow the listed verbs or block the listed verbs
+-rawfilter [add,remove] verb #Add/remove a command verb to the raw filter
+-rawfilter list #Show the raw filter
 ";
 
 #[derive(Debug, Clone)]
@@ -74,6 +169,14 @@ pub enum BotErrorKind {
     MessageRetrieveError,
     MessageEditError,
     DiscordError,
+    RawCommandDenied,
+    InvalidItem,
+    WorkshopError,
+    MigrationError,
+    BackupError,
+    ImportError,
+    StorageError,
+    InvalidServerInfo,
 }
 
 impl Display for BotErrorKind {
@@ -87,7 +190,7 @@ impl Display for BotErrorKind {
             BotErrorKind::VoteInProgress => "There's already a vote in progress",
             BotErrorKind::VoteNotInProgress => "There's no vote in progress",
             BotErrorKind::CouldNotReply => "Could not reply to the channel",
-            BotErrorKind::InvalidGameMode => "Invalid game mode valid valid [DM,TDM,GUN,WW2GUN,SND,WW2TDM,TANKTDM,TTT,KOTH]",
+            BotErrorKind::InvalidGameMode => "Invalid game mode",
             BotErrorKind::InvalidVoteAmount => "Can't set the vote choices to this number",
             BotErrorKind::ConnectionError => "Connection error",
             BotErrorKind::Authentication => "Authentication error with password: ",
@@ -102,6 +205,14 @@ impl Display for BotErrorKind {
             BotErrorKind::MessageEditError => "Could not edit message",
             BotErrorKind::VoteAuthorNotFound => { "Could not find vote author" }
             BotErrorKind::DiscordError => { "Error interacting with discord" }
+            BotErrorKind::RawCommandDenied => "Raw command not allowed",
+            BotErrorKind::InvalidItem => "Invalid item",
+            BotErrorKind::WorkshopError => "Workshop metadata error",
+            BotErrorKind::MigrationError => "Config migration error",
+            BotErrorKind::BackupError => "Config backup error",
+            BotErrorKind::ImportError => "Config import error",
+            BotErrorKind::StorageError => "Storage error",
+            BotErrorKind::InvalidServerInfo => "Invalid server info format",
         })
     }
 }
@@ -126,6 +237,9 @@ fn combine_trees(framework: &mut CustomFramework, ctx: &mut Context, msg: &mut M
         PermissionLevel::None => false
     };
     if !can_execute {
+        if first_argument == "raw" {
+            audit(msg.author.id.0, format!("raw command rejected for rank {}: \"{}\"", permission, arguments[1..].join(" ")));
+        }
         output(ctx, msg, format!("You're not allowed to execute the command: {}, your rank is currently {}", first_argument, permission));
         return Ok(());
     }
@@ -137,13 +251,35 @@ fn combine_trees(framework: &mut CustomFramework, ctx: &mut Context, msg: &mut M
         "bothelp" => output(ctx, msg, BOT_HELP.to_string()),
         "mod" => output(ctx, msg, handle_mod(arguments, &mut framework.config)?),
         "map" => handle_map(arguments, framework, msg, ctx, concurrent_framework)?,
-        "team" => output(ctx, msg, handle_team(arguments, framework)?),
+        "team" => {
+            let response = handle_team(arguments, framework, msg, &concurrent_framework)?;
+            output(ctx, msg, response);
+        }
         "vote" => output(ctx, msg, handle_vote_amount(arguments, &mut framework.config)?),
         "channel" => {
             let channel = handle_channel(arguments, msg, &mut framework.config)?;
             output(ctx, msg, channel);
         }
         "skin" => output(ctx, msg, handle_skin(arguments, framework)?),
+        "raw" => output(ctx, msg, handle_raw(arguments, framework, msg.author.id.0)?),
+        "rawfilter" => output(ctx, msg, handle_raw_filter(arguments, &mut framework.config)?),
+        "catalog" => output(ctx, msg, handle_catalog(arguments, &mut framework.config)?),
+        "pool" => output(ctx, msg, handle_pool(arguments, &mut framework.config)?),
+        "whitelist" => output(ctx, msg, handle_whitelist(arguments, &mut framework.config, msg.author.id.0)?),
+        "rules" => output(ctx, msg, handle_rules(arguments, framework, msg.author.id.0)?),
+        "economy" => output(ctx, msg, handle_economy(arguments, &mut framework.config, msg.author.id.0)?),
+        "matches" => output(ctx, msg, handle_matches(arguments, &framework.config)?),
+        "link" => output(ctx, msg, handle_link(arguments, framework, msg.author.id.0, permission)?),
+        "unlink" => output(ctx, msg, handle_unlink(framework, msg.author.id.0)?),
+        "me" => output(ctx, msg, handle_me(arguments, framework, msg.author.id.0)?),
+        "votekick" => handle_votekick(arguments, framework, msg, ctx, concurrent_framework, permission)?,
+        "config" => handle_config(arguments, framework, msg, ctx)?,
+        "giveitem" => output(ctx, msg, handle_give_item(arguments, framework)?),
+        "items" => output(ctx, msg, handle_items(arguments, framework)?),
+        "workshop" => {
+            let response = handle_workshop(arguments, msg, &mut framework.config)?;
+            output(ctx, msg, response);
+        }
         _ => {
             let command = PavlovCommands::parse_from_arguments(arguments, &framework.config)?;
             println!("{}", &command.to_string());
@@ -154,22 +290,121 @@ fn combine_trees(framework: &mut CustomFramework, ctx: &mut Context, msg: &mut M
     Ok(())
 }
 
-fn handle_team(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+fn handle_raw(arguments: &Vec<&str>, framework: &mut CustomFramework, author: u64) -> Result<String, IvanError> {
+    let command = PavlovCommands::parse_from_arguments(arguments, &framework.config).map_err(|err| {
+        audit(author, format!("raw command rejected: {}", err));
+        err
+    })?;
+    audit(author, format!("raw command executed: \"{}\"", command));
+    Ok(framework.connection.execute_command(command))
+}
+
+fn handle_raw_filter(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_RAW_FILTER)?.to_lowercase().as_str() {
+        "allow" => {
+            config.set_raw_filter_mode(RawFilterMode::Allow)?;
+            Ok("Raw commands are now only allowed for verbs on the filter list".to_string())
+        }
+        "deny" => {
+            config.set_raw_filter_mode(RawFilterMode::Deny)?;
+            Ok("Raw commands are now allowed unless their verb is on the filter list".to_string())
+        }
+        "add" => {
+            let verb = pa(arguments, 2, HELP_RAW_VERB)?;
+            config.add_raw_filter(verb.to_string())?;
+            Ok(format!("Added \"{}\" to the raw filter", verb))
+        }
+        "remove" => {
+            let verb = pa(arguments, 2, HELP_RAW_VERB)?;
+            config.remove_raw_filter(verb.to_string())?;
+            Ok(format!("Removed \"{}\" from the raw filter (if it existed)", verb))
+        }
+        "list" => Ok(format!("Raw filter mode: {}\n{}", config.get_raw_filter_mode(), make_message(config.get_raw_filter()))),
+        x => invalid_argument(x, HELP_RAW_FILTER)
+    }
+}
+
+fn handle_team(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &Message, ctx: &ConcurrentFramework) -> Result<String, IvanError> {
     let argument = pa(arguments, 1, HELP_TEAM_MODES)?;
 
     return match argument {
         "channels" => handle_team_channels(arguments, &mut framework.config),
+        "lobby" => {
+            let channel = parse_number(pa(arguments, 2, HELP_TEAM_CHANNEL)?)?;
+            framework.config.set_lobby_channel(channel)?;
+            Ok(format!("added lobby channel {}", channel))
+        }
+        "split" => team_split(framework, msg, ctx),
+        "merge" => team_merge(framework, ctx),
+        "swap" => team_swap(arguments, framework, ctx),
+        "autobalance" => handle_autobalance(arguments, &mut framework.config, msg.author.id.0),
         _ => Err(IvanError { input: format!("{}", HELP_TEAM_MODES), kind: BotErrorKind::InvalidArgument }),
     };
 }
 
-fn get_player_list(framework: &mut CustomFramework) -> Result<Vec<Player>, IvanError> {
+type VoiceChannel = Arc<RwLock<GuildChannel>>;
+
+fn team_channels(framework: &CustomFramework, ctx: &ConcurrentFramework) -> Result<(VoiceChannel, VoiceChannel), IvanError> {
+    let (channel1, channel2) = framework.config.get_team_channels().ok_or_else(|| {
+        IvanError { input: "no team channels configured, use -team channels".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    Ok((get_channel(ctx, channel1)?, get_channel(ctx, channel2)?))
+}
+
+/// Randomly splits the voice channel of the author over the two team channels.
+fn team_split(framework: &mut CustomFramework, msg: &Message, ctx: &ConcurrentFramework) -> Result<String, IvanError> {
+    let (red, blue) = team_channels(framework, ctx)?;
+    let guild_id = msg.guild_id.ok_or_else(|| {
+        IvanError { input: "use -team split in a server channel".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    let lobby = get_voice_channel(ctx, guild_id, msg.author.id.0)?.ok_or_else(|| {
+        IvanError { input: "join a voice channel first, the people in it are split".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    let mut users = channel_members(ctx, &lobby)?;
+    users.shuffle(&mut rand::thread_rng());
+    let blue_team = users.split_off(users.len() / 2);
+    move_to_channel(ctx, lobby.clone(), red, users.clone())?;
+    move_to_channel(ctx, lobby, blue, blue_team.clone())?;
+    Ok(format!("Split {} players into the team channels", users.len() + blue_team.len()))
+}
+
+/// Moves everyone in the team channels to the lobby channel.
+fn team_merge(framework: &mut CustomFramework, ctx: &ConcurrentFramework) -> Result<String, IvanError> {
+    let (red, blue) = team_channels(framework, ctx)?;
+    let lobby = framework.config.get_lobby_channel().ok_or_else(|| {
+        IvanError { input: "no lobby channel configured, use -team lobby".to_string(), kind: BotErrorKind::InvalidArgument }
+    })?;
+    let lobby = get_channel(ctx, lobby)?;
+    let mut moved = 0;
+    for channel in [red, blue].iter() {
+        let users = channel_members(ctx, channel)?;
+        moved += users.len();
+        move_to_channel(ctx, channel.clone(), lobby.clone(), users)?;
+    }
+    Ok(format!("Moved {} players to the lobby", moved))
+}
+
+/// Moves a user from one team channel to the other.
+fn team_swap(arguments: &Vec<&str>, framework: &mut CustomFramework, ctx: &ConcurrentFramework) -> Result<String, IvanError> {
+    let user = parse_discord_id(pa(arguments, 2, HELP_TEAM_SWAP)?)?;
+    let (red, blue) = team_channels(framework, ctx)?;
+    if channel_members(ctx, &red)?.contains(&user) {
+        move_to_channel(ctx, red, blue, vec![user])?;
+    } else if channel_members(ctx, &blue)?.contains(&user) {
+        move_to_channel(ctx, blue, red, vec![user])?;
+    } else {
+        return Err(IvanError { input: format!("<@{}> is not in one of the team channels", user), kind: BotErrorKind::InvalidArgument });
+    }
+    Ok(format!("Swapped <@{}> to the other team", user))
+}
+
+pub fn get_player_list(framework: &mut CustomFramework) -> Result<Vec<Player>, IvanError> {
     let players_string = framework.connection.execute_command(PavlovCommands::RefreshList);
     let player = serde_json::from_str::<Players>(players_string.as_str()).map_err(|err| IvanError { input: format!("tried to get players but failed {}", err), kind: BotErrorKind::InvalidPlayerList })?;
     Ok(player.PlayerList)
 }
 
-fn inspect_all(player: Vec<Player>, framework: &mut CustomFramework) -> Result<Vec<PlayerInfo>, IvanError> {
+pub fn inspect_all(player: Vec<Player>, framework: &mut CustomFramework) -> Result<Vec<PlayerInfo>, IvanError> {
     let player_list: Vec<Result<PlayerInfo, IvanError>> = player.iter().map(|value| {
         inspect_player(value, framework)
     }).collect();
@@ -231,11 +466,15 @@ fn switch_team(inspect_list: Vec<PlayerInfo>, move_to_team: u32, player_amount:
     list.iter().fold("".to_string(), |a, b| format!("{}\n{}", a, b))
 }
 
-fn inspect_player(player: &Player, framework: &mut CustomFramework) -> Result<PlayerInfo, IvanError> {
+pub fn inspect_player(player: &Player, framework: &mut CustomFramework) -> Result<PlayerInfo, IvanError> {
     let inspect = framework.connection.execute_command(PavlovCommands::InspectPlayer(parse_number(player.UniqueId.as_str())?));
     Ok(serde_json::from_str::<PlayerInfoContainer>(inspect.as_str()).map_err(|err| IvanError { input: format!("could not parse PlayerInfo because of {}", err.to_string()), kind: BotErrorKind::InvalidPlayerList })?.PlayerInfo)
 }
 
+pub fn get_server_info(framework: &mut CustomFramework) -> Result<ServerInfo, IvanError> {
+    let info = framework.connection.execute_command(PavlovCommands::ServerInfo);
+    Ok(serde_json::from_str::<ServerInfoContainer>(info.as_str()).map_err(|err| IvanError { input: format!("could not parse ServerInfo because of {}", err), kind: BotErrorKind::InvalidServerInfo })?.ServerInfo)
+}
 
 fn handle_team_channels(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
     let channel1 = parse_number(pa(arguments, 2, HELP_TEAM_CHANNEL)?)?;
@@ -304,6 +543,8 @@ fn handle_gunmode(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<Stri
 fn handle_skin(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
     match pa(arguments, 1, HELP_SKIN_TEAM)? {
         "shuffle" => handle_skin_shuffle(arguments, framework),
+        "theme" => handle_skin_theme(arguments, framework),
+        "team" => handle_skin_team(arguments, framework),
         "random" => {
             let random = || -> Skin {
                 Skin::get_random()
@@ -317,6 +558,9 @@ fn handle_skin(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result
         "russian" => assign_skins(framework, || { Skin::Russian }),
         "nato" => assign_skins(framework, || { Skin::Nato }),
         "german" => assign_skins(framework, || { Skin::German }),
+        "soviet" => assign_skins(framework, || { Skin::Soviet }),
+        "us" => assign_skins(framework, || { Skin::Us }),
+        "cop" => assign_skins(framework, || { Skin::Cop }),
         x => {
             invalid_argument(x, HELP_SKIN_TEAM)
         }
@@ -372,12 +616,12 @@ pub fn reply(msg: &mut Message, cache_http: &Http, message: String) -> Result<Me
     })
 }
 
-fn check_alias(value: &str) -> Result<String, IvanError> {
-    let regex = Regex::new("[A-z0-9]{3}[A-z0-9]*").unwrap();
+pub fn check_alias(value: &str) -> Result<String, IvanError> {
+    let regex = Regex::new("^[A-Za-z0-9_-]{3,}$").unwrap();
     match regex.is_match(value) {
         true => Ok(value.to_string()),
         false => Err(IvanError {
-            input: value.to_string(),
+            input: format!("\"{}\" {}", value, HELP_ALIAS),
             kind: BotErrorKind::InvalidMapAlias,
         })
     }
@@ -394,6 +638,14 @@ fn handle_channel(arguments: &Vec<&str>, msg: &mut Message, config: &mut IvanCon
             config.remove_channel_lock()?;
             Ok("removed channel lock".to_string())
         }
+        "log" => {
+            config.set_log_channel(Some(msg.channel_id.0))?;
+            Ok(format!("The bot will post its own messages in: {}", msg.channel_id))
+        }
+        "unlog" => {
+            config.set_log_channel(None)?;
+            Ok("The bot will post its own messages in the locked channel (if there is one)".to_string())
+        }
         x => invalid_argument(x, HELP_CHANNEL_MODE)
     }
 }
@@ -404,9 +656,31 @@ fn handle_map(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut
     match first.to_lowercase().as_str() {
         "add" => map_add(arguments, framework, msg, ctx),
         "remove" => map_remove(arguments, framework, msg, ctx),
+        "edit" => {
+            let response = map_edit(arguments, &mut framework.config)?;
+            output(ctx, msg, response);
+            Ok(())
+        }
+        "enable" | "disable" => {
+            let alias_or_map = pa(arguments, 2, HELP_ALIAS_OR_MAP)?;
+            let enabled = first.eq_ignore_ascii_case("enable");
+            framework.config.set_map_enabled(alias_or_map, enabled)?;
+            output(ctx, msg, format!("Map \"{}\" {}", alias_or_map, if enabled { "enabled" } else { "disabled, it stays in the pool but is left out of votes" }));
+            Ok(())
+        }
+        "info" => {
+            let response = map_info(arguments, &framework.config)?;
+            output(ctx, msg, response);
+            Ok(())
+        }
+        "action" => {
+            let response = handle_map_action(arguments, &mut framework.config)?;
+            output(ctx, msg, response);
+            Ok(())
+        }
         "vote" => handle_vote(arguments, framework, msg, ctx, concurrent_framework),
-        "list" => handle_map_pool(framework, msg, ctx),
-        "default" => Ok(output(ctx, msg, format_default_maps())),
+        "list" => handle_map_pool(arguments, framework, msg, ctx),
+        "default" => Ok(output(ctx, msg, format_default_maps(&framework.config))),
         command => {
             invalid_argument(command, HELP_MAP_ARGUMENT)?;
             Ok(())
@@ -414,19 +688,81 @@ fn handle_map(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut
     }
 }
 
-fn format_default_maps() -> String {
-    DEFAULT_MAPS.iter().fold("".to_string(), |a, b| {
+fn format_default_maps(config: &IvanConfig) -> String {
+    config.get_catalog().maps.iter().fold("".to_string(), |a, b| {
         format!("{}\n{}", a, b)
     })
 }
 
-fn handle_map_pool(framework: &mut CustomFramework, msg: &mut Message, ctx: &Context) -> Result<(), IvanError> {
-    let maps = framework.config.get_maps();
-    let message = "The map pool is currently:\n".to_string().add(make_message(maps).as_str());
+/// `-map list (gamemode) (page)`
+fn handle_map_pool(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &Context) -> Result<(), IvanError> {
+    let mut gamemode: Option<GameMode> = None;
+    let mut page: usize = 1;
+    for argument in arguments.iter().skip(2) {
+        match argument.parse::<usize>() {
+            Ok(number) => page = number,
+            Err(_) => gamemode = Some(parse_game_mode(argument, &framework.config)?)
+        }
+    }
+    let maps: Vec<String> = framework.config.get_maps().iter().filter(|map| {
+        gamemode.as_ref().map(|gamemode| *gamemode == map.gamemode).unwrap_or(true)
+    }).map(|map| framework.config.format_pool_map(map)).collect();
+    let pages = max(1, maps.chunks(MAP_PAGE_SIZE).count());
+    if page < 1 || page > pages {
+        return Err(IvanError { input: format!("page {}, the map list has {} page(s)", page, pages), kind: BotErrorKind::InvalidArgument });
+    }
+    let shown: Vec<String> = maps.into_iter().skip((page - 1) * MAP_PAGE_SIZE).take(MAP_PAGE_SIZE).collect();
+    let message = format!("The map pool is currently (page {} of {}):\n", page, pages).add(make_message(&shown).as_str());
     reply(msg, ctx.http(), format!("{}", message))?;
     Ok(())
 }
 
+/// `-map edit alias gamemode=MODE alias=NEW_ALIAS`
+fn map_edit(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    let alias_or_map = pa(arguments, 2, HELP_ALIAS_OR_MAP)?;
+    pa(arguments, 3, HELP_MAP_EDIT)?;
+    let mut gamemode: Option<GameMode> = None;
+    let mut alias: Option<String> = None;
+    for argument in arguments.iter().skip(3) {
+        let parts: Vec<&str> = argument.splitn(2, '=').collect();
+        match parts.as_slice() {
+            [key, value] if key.eq_ignore_ascii_case("gamemode") => gamemode = Some(parse_game_mode(value, config)?),
+            [key, value] if key.eq_ignore_ascii_case("alias") => alias = Some(check_alias(value)?),
+            _ => return invalid_argument(argument, HELP_MAP_EDIT)
+        }
+    }
+    let found_by = alias.clone().unwrap_or_else(|| alias_or_map.to_string());
+    config.edit_map(alias_or_map, gamemode, alias)?;
+    Ok(match config.find_pool_map(&found_by) {
+        Some(map) => format!("Map edited: {}", config.format_pool_map(map)),
+        None => "Map edited".to_string()
+    })
+}
+
+fn map_info(arguments: &Vec<&str>, config: &IvanConfig) -> Result<String, IvanError> {
+    let alias_or_map = pa(arguments, 2, HELP_ALIAS_OR_MAP)?;
+    let map = config.find_pool_map(alias_or_map).ok_or_else(|| {
+        IvanError { input: alias_or_map.to_string(), kind: InvalidMapAlias }
+    })?;
+    let mut info = format!("alias: {}\nmap: {}\ngamemode: {}\nin votes: {}", map.alias, map.map, map.gamemode, if map.enabled { "yes" } else { "no, disabled" });
+    if let Some(economy) = &map.economy {
+        info = format!("{}\neconomy: {}", info, economy);
+    }
+    if !map.actions.is_empty() {
+        info = format!("{}\nactions: {}", info, map.actions.iter().map(|action| action.to_string()).collect::<Vec<String>>().join(", "));
+    }
+    if let Some(metadata) = config.find_workshop_metadata(&map.map) {
+        info = format!("{}\ntitle: {}\nsize: {} MB", info, metadata.title, metadata.file_size / 1_000_000);
+        if !metadata.modes.is_empty() {
+            info = format!("{}\nworkshop modes: {}", info, metadata.modes.join(", "));
+        }
+    }
+    if map.map.starts_with("UGC") {
+        info = format!("{}\nhttps://steamcommunity.com/sharedfiles/filedetails/?id={}", info, map.map.trim_start_matches("UGC"));
+    }
+    Ok(info)
+}
+
 fn make_message<T: Display>(maps: &Vec<T>) -> String {
     let message = maps.iter().fold("".to_string(), |a, b| { format!("{}\n{}", a, b.to_string()) });
     message
@@ -447,9 +783,17 @@ fn handle_vote_amount(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<
 fn handle_vote(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &mut Context, concurrent_framework: ConcurrentFramework) -> Result<(), IvanError> {
 //    let second = pa(arguments, 2, HELP_VOTE_ARGUMENT)?;
 
-    let game_mode = match pa(arguments, 2, HELP_GAMEMODE) {
-        Ok(value) => { Option::Some(parse_game_mode(value)?) }
-        Err(_) => { Option::None }
+    if arguments.get(2).map(|value| value.eq_ignore_ascii_case("modes")).unwrap_or(false) {
+        let users = get_users_from_channel(msg, ctx).unwrap_or_else(|value| {
+            println!("Couldn't retrieve users from channel {}", value);
+            vec![]
+        });
+        return handle_mode_vote_start(framework, msg, concurrent_framework, users);
+    }
+
+    let game_mode = match arguments.get(2) {
+        Some(value) => { Option::Some(parse_game_mode(value, &framework.config)?) }
+        None => { Option::None }
     };
 
 
@@ -460,7 +804,7 @@ fn handle_vote(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut
 
 
     let teams = match pa(arguments, 3, HELP_TEAM_CREATE) {
-        Ok(value) => Some(parse_team_create(value, game_mode)?),
+        Ok(value) => Some(parse_team_create(value, &game_mode, &framework.config)?),
         Err(_) => None
     }.map(|_| {
         let team1: Vec<u64> = users.choose_multiple(&mut rand::thread_rng(), users.len() / 2).cloned().collect();
@@ -472,13 +816,10 @@ fn handle_vote(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut
     return Ok(handle_vote_start(framework, msg, ctx, concurrent_framework, game_mode, users, teams)?);
 }
 
-fn parse_team_create(value: &str, game_mode: Option<GameMode>) -> Result<(), IvanError> {
+fn parse_team_create(value: &str, game_mode: &Option<GameMode>, config: &IvanConfig) -> Result<(), IvanError> {
     if value.to_lowercase().eq("teams") {
         match game_mode {
-            Some(GameMode::TDM) => Ok(()),
-            Some(GameMode::KOTH) => Ok(()),
-            Some(GameMode::TANKTDM) => Ok(()),
-            Some(GameMode::SND) => Ok(()),
+            Some(mode) if config.get_catalog().is_team_mode(mode) => Ok(()),
             _ => Err(IvanError { input: "can only add teams argument to map votes with team gamemode".to_string(), kind: BotErrorKind::InvalidArgument })
         }
     } else {
@@ -499,9 +840,34 @@ fn handle_alias(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String
         }
         "remove" => {
             let argument = pa(arguments, 2, HELP_ALIAS)?;
+            let users = config.get_alias_users(argument).len();
+            if users > 0 && arguments.get(3).map(|value| value.to_lowercase()) != Some("confirm".to_string()) {
+                return Err(IvanError {
+                    input: format!("alias \"{}\" is used by {} pool map(s), use -alias remove {} confirm to remove it anyway", argument, users, argument),
+                    kind: BotErrorKind::InvalidArgument,
+                });
+            }
             config.remove_alias(argument.to_string())?;
             Ok(format!("alias \"{}\" removed (if it existed)", argument))
         }
+        "rename" => {
+            let alias = pa(arguments, 2, HELP_ALIAS)?;
+            let new_alias = check_alias(pa(arguments, 3, HELP_ALIAS)?)?;
+            config.rename_alias(alias, new_alias.clone())?;
+            Ok(format!("alias \"{}\" renamed to \"{}\"", alias, new_alias))
+        }
+        "show" => {
+            let alias = pa(arguments, 2, HELP_ALIAS)?;
+            let map = config.resolve_alias(alias).ok_or_else(|| {
+                IvanError { input: alias.to_string(), kind: InvalidMapAlias }
+            })?;
+            let users = config.get_alias_users(alias);
+            let pool = match users.is_empty() {
+                true => "not used in the map pool".to_string(),
+                false => users.iter().fold("used in the map pool by:".to_string(), |a, map| format!("{}\n{}", a, map))
+            };
+            Ok(format!("alias: {} map: {}\n{}", alias, map, pool))
+        }
         "list" => {
             Ok(make_message(&config.get_alias_list()))
         }
@@ -510,8 +876,11 @@ fn handle_alias(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String
 }
 
 fn map_add(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &Context) -> Result<(), IvanError> {
+    if let Ok(gamemode) = parse_game_mode(pa(arguments, 2, HELP_MAP)?, &framework.config) {
+        return map_add_bulk(arguments, gamemode, framework, msg, ctx);
+    }
     let map = parse_map(pa(arguments, 2, HELP_MAP)?, &framework.config)?;
-    let gamemode = parse_game_mode(pa(arguments, 3, HELP_GAMEMODE)?).map_err(|err| {
+    let gamemode = parse_game_mode(pa(arguments, 3, &framework.config.get_catalog().help_game_modes())?, &framework.config).map_err(|err| {
         IvanError { input: err.input, kind: BotErrorKind::InvalidGameMode }
     })?;
     let alias = check_alias(pa(arguments, 4, HELP_ALIAS)?)?;
@@ -521,14 +890,30 @@ fn map_add(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Mes
     Ok(())
 }
 
+/// `-map add GAMEMODE {url/map} alias {url/map} alias ...` adds several maps with the same game mode.
+fn map_add_bulk(arguments: &[&str], gamemode: GameMode, framework: &mut CustomFramework, msg: &mut Message, ctx: &Context) -> Result<(), IvanError> {
+    let pairs = &arguments[3..];
+    if pairs.is_empty() || pairs.chunks(2).any(|pair| pair.len() != 2) {
+        return Err(IvanError { input: HELP_MAP_BULK.to_string(), kind: BotErrorKind::MissingArgument });
+    }
+    let maps = pairs.chunks(2).map(|pair| {
+        Ok(PoolMap::new(parse_map(pair[0], &framework.config)?, gamemode.clone(), check_alias(pair[1])?))
+    }).collect::<Result<Vec<PoolMap>, IvanError>>()?;
+    let added = maps.iter().fold(format!("Added {} maps to the pool with gamemode \"{}\":", maps.len(), gamemode), |a, map| {
+        format!("{}\n{} ({})", a, map.alias, map.map)
+    });
+    framework.config.add_maps(maps)?;
+    reply(msg, ctx.http(), added)?;
+    Ok(())
+}
+
 fn map_remove(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &Context) -> Result<(), IvanError> {
     if framework.vote.is_some() {
         return Err(IvanError { input: "Can't remove a map when a vote is in progress".to_string(), kind: BotErrorKind::VoteInProgress }.into());
     }
     let alias_or_map = pa(arguments, 2, HELP_ALIAS_OR_MAP)?;
-    framework.config.remove_alias(alias_or_map.to_string())?;
     framework.config.remove_map(alias_or_map.to_string())?;
-    handle_map_pool(framework, msg, ctx)
+    handle_map_pool(&vec![], framework, msg, ctx)
 }
 
 
@@ -1,8 +1,9 @@
 use crate::model::{IvanError, BotErrorKind};
 
 
+/// Parses a discord id, mentions like <@123> and <@!123> are accepted as well.
 pub fn parse_discord_id(value: &str) -> Result<u64, IvanError> {
-    value.parse::<u64>().map_err(|_error| {
+    value.trim_start_matches("<@").trim_start_matches('!').trim_end_matches('>').parse::<u64>().map_err(|_error| {
         IvanError { input: format!("Invalid discord id \"{}\"",value), kind: BotErrorKind::InvalidArgument }
     })
 }
@@ -1,14 +1,13 @@
 use crate::pavlov::PavlovCommands::{Help, Ban, Kick, RotateMap, SwitchMap, Unban, GiveItem, GiveCash, GiveTeamCash, InspectPlayer, RefreshList, ServerInfo, ResetSND, SetPlayerSkin, SetLimitedAmmoType, SwitchTeam, BlackList, MapList, SetCash, ItemList, Kill, Raw, AddMod, RemoveMod};
 use std::fmt::{Display, Formatter};
 use core::fmt;
-use crate::pavlov::GameMode::{SND, TDM, DM, GUN, WW2GUN, TANKTDM, KOTH, TTT, WW2TDM, OITC};
 use crate::pavlov::Skin::{Clown, Prisoner, Naked, Russian, Farmer, Nato, German, Soviet, Us};
 use regex::{Regex};
 use crate::config::IvanConfig;
 use serde::{Serialize, Deserialize};
 use serenity::static_assertions::_core::str::FromStr;
 use rand::seq::SliceRandom;
-use crate::help::{HELP_GAMEMODE, HELP_MAP, HELP_VALID_TEAM, HELP_STEAM_ID, HELP_LIMITED_AMMO, HELP_SKIN, HELP_CASH, HELP_ITEM};
+use crate::help::{HELP_MAP, HELP_VALID_TEAM, HELP_STEAM_ID, HELP_LIMITED_AMMO, HELP_SKIN, HELP_CASH, HELP_ITEM};
 use crate::model::{BotErrorKind, IvanError};
 use crate::model::BotErrorKind::{InvalidCommand, MissingArgument, InvalidArgument, InvalidMap};
 
@@ -44,41 +43,34 @@ pub enum PavlovCommands {
 
 }
 
-pub const DEFAULT_MAPS: [&'static str; 13] = [
-    "datacenter",
-    "sand",
-    "bridge",
-    "containeryard",
-    "prisonbreak",
-    "hospital",
-    "killhouse",
-    "range",
-    "tutorial",
-    "station",
-    "stalingrad",
-    "santorini",
-    "industry"
-];
+/// Name of a game mode, the valid names come from the catalog (see `catalog.rs`).
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+#[serde(transparent)]
+pub struct GameMode(String);
+
+impl GameMode {
+    pub const GUN: &'static str = "GUN";
+    pub const WW2GUN: &'static str = "WW2GUN";
+    pub const OITC: &'static str = "OITC";
+
+    pub fn named(name: &str) -> GameMode {
+        GameMode(name.to_uppercase())
+    }
 
+    pub fn name(&self) -> &str {
+        self.0.as_str()
+    }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Copy)]
-pub enum GameMode {
-    SND,
-    DM,
-    TDM,
-    GUN,
-    WW2GUN,
-    TANKTDM,
-    KOTH,
-    TTT,
-    WW2TDM,
-    OITC,
+    pub fn is(&self, name: &str) -> bool {
+        self.0.eq_ignore_ascii_case(name)
+    }
 }
 
 pub type SteamId = u64;
 pub type TeamId = u32;
 
-#[derive(Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Skin {
     Clown,
     Prisoner,
@@ -128,7 +120,7 @@ impl PavlovCommands {
             "rotatemap" | "maprotate" => RotateMap,
             "switchmap" | "mapswitch" | "switch" => SwitchMap {
                 map: parse_map(pa(arguments, 1, HELP_MAP)?, config)?,
-                gamemode: parse_game_mode(pa(arguments, 2, HELP_GAMEMODE)?)?,
+                gamemode: parse_game_mode(pa(arguments, 2, &config.get_catalog().help_game_modes())?, config)?,
             },
             "switchteam" => SwitchTeam(parse_number(pa(arguments, 1, HELP_STEAM_ID)?)?, parse_team(pa(arguments, 2, HELP_VALID_TEAM)?)?),
             "setcash" => SetCash(parse_number(pa(arguments, 1, HELP_STEAM_ID)?)?, parse_number(pa(arguments, 2, HELP_CASH)?)?),
@@ -142,7 +134,7 @@ impl PavlovCommands {
             "resetsnd" => ResetSND,
             "setplayerskin" => SetPlayerSkin(parse_number(pa(arguments, 1, HELP_STEAM_ID)?)?, parse_skin(pa(arguments, 2, HELP_SKIN)?)?),
             "setlimitedammotype" => SetLimitedAmmoType(pa(arguments, 1, HELP_LIMITED_AMMO)?.to_string()),
-            //    "raw" => Raw(handle_raw(arguments)?),
+            "raw" => Raw(handle_raw(arguments, config)?),
             x => return Err(IvanError { input: x.to_string(), kind: InvalidCommand })
         };
         return Ok(command);
@@ -169,7 +161,7 @@ pub fn parse_map(value: &str, config: &IvanConfig) -> Result<String, IvanError>
 
     let map = map_string.to_lowercase();
     let map_str = map.as_str();
-    if is_standard_map(map_str) { return Ok(map_string); }
+    if is_standard_map(map_str, config) { return Ok(map_string); }
     let steam_workshop_regex: Regex = Regex::new("id=([0-9]+)").unwrap();
     let valid_mapname: Regex = Regex::new("[UGC]*[0-9]+").unwrap();
     if map_string.contains("steamcommunity.com") {
@@ -187,15 +179,15 @@ pub fn parse_map(value: &str, config: &IvanConfig) -> Result<String, IvanError>
     }
 }
 
-fn is_standard_map(map: &str) -> bool {
-    return DEFAULT_MAPS.contains(&map);
+fn is_standard_map(map: &str, config: &IvanConfig) -> bool {
+    return config.get_catalog().is_map(map);
 }
 
 fn parse_team(value: &str) -> Result<u32, IvanError> {
     parse_number(value)
 }
 
-fn parse_skin<'a>(value: &str) -> Result<Skin, IvanError> {
+pub fn parse_skin<'a>(value: &str) -> Result<Skin, IvanError> {
     let skin = match value.to_lowercase().as_str() {
         "clown" => Clown,
         "prisoner" => Prisoner,
@@ -206,41 +198,42 @@ fn parse_skin<'a>(value: &str) -> Result<Skin, IvanError> {
         "german" => German,
         "soviet" => Soviet,
         "us" => Us,
+        "cop" => Skin::Cop,
         x => return Result::Err(IvanError { input: format!("Invalid skin \"{}\" {}", x, HELP_SKIN), kind: BotErrorKind::InvalidArgument })
     };
     Ok(skin)
 }
 
-pub fn parse_game_mode(value: &str) -> Result<GameMode, IvanError> {
-    let result = match value.to_lowercase().as_str() {
-        "snd" => SND,
-        "dm" => DM,
-        "tdm" => TDM,
-        "gun" => GUN,
-        "ww2gun" => WW2GUN,
-        "tanktdm" => TANKTDM,
-        "koth" => KOTH,
-        "ttt" => TTT,
-        "ww2tdm" => WW2TDM,
-        "oitc" => OITC,
-        x => return Err(IvanError { input: format!("Invalid game mode \"{}\" {}", x, HELP_GAMEMODE), kind: BotErrorKind::InvalidArgument })
-    };
-    Ok(result)
+pub fn parse_game_mode(value: &str, config: &IvanConfig) -> Result<GameMode, IvanError> {
+    let catalog = config.get_catalog();
+    match catalog.find_mode(value) {
+        Some(mode) => Ok(GameMode::named(mode.name.as_str())),
+        None => Err(IvanError { input: format!("Invalid game mode \"{}\" {}", value.to_lowercase(), catalog.help_game_modes()), kind: BotErrorKind::InvalidArgument })
+    }
 }
 
-fn handle_raw(arguments: &Vec<&str>) -> Result<String, IvanError> {
+fn handle_raw(arguments: &Vec<&str>, config: &IvanConfig) -> Result<String, IvanError> {
     let slice = arguments[1..arguments.len()].to_vec();
     let iter = slice.iter();
     let concat = iter.fold("".to_string(), |a, b| format!("{} {}", a, b));
+    // a newline would let the rest of the input be executed as a second command
+    let single_line = concat.replace(|c: char| c.is_control(), " ");
 
-    let without_prefix = concat.strip_prefix(" ");
-    match without_prefix {
-        Some(value) => Ok(value.to_string()),
-        None => Err(IvanError {
+    let without_prefix = single_line.trim();
+    if without_prefix.is_empty() {
+        return Err(IvanError {
             input: "Raw input was empty".to_string(),
             kind: BotErrorKind::InvalidArgument,
-        })
+        });
+    }
+    let verb = without_prefix.split_whitespace().next().unwrap_or("");
+    if !config.is_raw_verb_allowed(verb) {
+        return Err(IvanError {
+            input: format!("\"{}\" is blocked by the raw command filter ({} list)", verb, config.get_raw_filter_mode()),
+            kind: BotErrorKind::RawCommandDenied,
+        });
     }
+    Ok(without_prefix.to_string())
 }
 
 
@@ -256,7 +249,7 @@ impl Display for Skin {
             German => "german",
             Soviet => "soviet",
             Us => "us",
-            Cop => "cop"
+            Skin::Cop => "cop"
         };
         write!(f, "{}", value)
     }
@@ -264,19 +257,7 @@ impl Display for Skin {
 
 impl Display for GameMode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let value = match self {
-            SND => "SND",
-            DM => "DM",
-            TDM => "TDM",
-            GUN => "GUN",
-            WW2GUN => "WW2GUN",
-            TANKTDM => "TANKTDM",
-            KOTH => "KOTH",
-            TTT => "TTT",
-            WW2TDM => "WW2TDM",
-            GameMode::OITC => { "OITC" }
-        };
-        write!(f, "{}", value)
+        write!(f, "{}", self.0)
     }
 }
 
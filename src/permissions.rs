@@ -68,14 +68,17 @@ pub fn mod_allowed(argument: String) -> bool {
         "rotatemap" |
         "alias" |
         "map" |
+        "workshop" |
         "maplist" |
         "switchteam" |
         "giveitem" |
+        "items" |
         "givecash" |
         "setCash" |
         "resetsnd" |
         "setplayerskin" |
-        "setlimitedammotype"
+        "setlimitedammotype" |
+        "whitelist"
         => true,
         _ => false
     }
@@ -83,7 +86,7 @@ pub fn mod_allowed(argument: String) -> bool {
 
 pub fn user_allowed(argument: String) -> bool {
     match argument.as_str() {
-        "inspectplayer" | "serverinfo" | "refreshlist" | "bothelp"| "maplist" => true,
+        "inspectplayer" | "serverinfo" | "refreshlist" | "bothelp"| "maplist" | "votekick" | "link" | "unlink" | "me" | "matches" => true,
         _ => false
     }
 }
@@ -0,0 +1,220 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::config::IvanConfig;
+use crate::model::{IvanError, BotErrorKind, invalid_argument};
+use crate::pavlov::{pa, parse_number};
+use crate::help::{HELP_POOL_ARGUMENT, HELP_POOL_NAME, HELP_POOL_SCHEDULE, HELP_POOL_TIME, HELP_ALIAS};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// A named selection of pool maps, for example the maps of a themed night.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MapPool {
+    pub name: String,
+    /// Aliases of the pool maps in this pool.
+    pub maps: Vec<String>,
+}
+
+/// Switches the active pool at a time of day (UTC), optionally only on one day of the week.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PoolSchedule {
+    pub time: String,
+    #[serde(default)]
+    pub weekday: Option<String>,
+    pub pool: String,
+}
+
+impl MapPool {
+    pub fn contains(&self, alias: &str) -> bool {
+        self.maps.iter().any(|value| value.eq_ignore_ascii_case(alias))
+    }
+}
+
+impl PoolSchedule {
+    fn is_due(&self, weekday: usize, minute_of_day: u64) -> bool {
+        let day_matches = match &self.weekday {
+            Some(day) => WEEKDAYS.get(weekday).map(|value| value.eq_ignore_ascii_case(day)).unwrap_or(false),
+            None => true
+        };
+        day_matches && parse_time(&self.time).map(|minute| minute == minute_of_day).unwrap_or(false)
+    }
+}
+
+/// Minutes since the unix epoch, the background thread runs the schedules once per minute.
+pub fn current_minute() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|value| value.as_secs() / 60).unwrap_or(0)
+}
+
+/// The pool of the last schedule that was due after `after` and up to `minute` (minutes since the
+/// unix epoch). A check that runs late still switches for the minutes it skipped, at most a day back.
+pub fn due_pool(schedules: &[PoolSchedule], after: u64, minute: u64) -> Option<String> {
+    let first = std::cmp::max(after + 1, minute.saturating_sub(24 * 60 - 1));
+    (first..=minute).rev().find_map(|minute| scheduled_pool(schedules, minute))
+}
+
+/// The pool that should become active in `minute`, if any.
+fn scheduled_pool(schedules: &[PoolSchedule], minute: u64) -> Option<String> {
+    let minute_of_day = minute % (24 * 60);
+    // the unix epoch was a thursday
+    let weekday = ((minute / (24 * 60) + 3) % 7) as usize;
+    schedules.iter().find(|schedule| schedule.is_due(weekday, minute_of_day)).map(|schedule| schedule.pool.clone())
+}
+
+pub fn handle_pool(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_POOL_ARGUMENT)?.to_lowercase().as_str() {
+        "list" => {
+            let active = config.get_active_pool().map(|value| value.to_string());
+            let pools = config.get_pools().iter().fold("".to_string(), |a, pool| {
+                let marker = if active.as_deref().map(|value| value.eq_ignore_ascii_case(&pool.name)).unwrap_or(false) { " (active)" } else { "" };
+                format!("{}\n{}{} \t{} maps", a, pool.name, marker, pool.maps.len())
+            });
+            Ok(match active {
+                Some(_) => format!("Pools:{}", pools),
+                None => format!("Pools:{}\nNo pool is active, votes use the whole map pool", pools)
+            })
+        }
+        "create" => {
+            let name = pa(arguments, 2, HELP_POOL_NAME)?;
+            config.create_pool(name.to_string())?;
+            Ok(format!("Created pool \"{}\", add maps with -pool add {} alias", name, name))
+        }
+        "delete" => {
+            let name = pa(arguments, 2, HELP_POOL_NAME)?;
+            config.delete_pool(name)?;
+            Ok(format!("Deleted pool \"{}\" and its schedules", name))
+        }
+        "add" | "remove" => {
+            let name = pa(arguments, 2, HELP_POOL_NAME)?;
+            pa(arguments, 3, HELP_ALIAS)?;
+            let aliases: Vec<String> = arguments.iter().skip(3).map(|value| value.to_string()).collect();
+            if arguments[1].eq_ignore_ascii_case("add") {
+                config.add_to_pool(name, aliases)?;
+                Ok(format!("Added the maps to pool \"{}\"", name))
+            } else {
+                config.remove_from_pool(name, aliases)?;
+                Ok(format!("Removed the maps from pool \"{}\"", name))
+            }
+        }
+        "use" => {
+            let name = pa(arguments, 2, HELP_POOL_NAME)?;
+            if name.eq_ignore_ascii_case("all") {
+                config.use_pool(None)?;
+                return Ok("Votes use the whole map pool again".to_string());
+            }
+            config.use_pool(Some(name.to_string()))?;
+            Ok(format!("Votes now use pool \"{}\"", name))
+        }
+        "show" => {
+            let name = pa(arguments, 2, HELP_POOL_NAME)?;
+            let pool = config.find_pool(name).ok_or_else(|| unknown_pool(name))?;
+            Ok(pool.maps.iter().fold(format!("Pool \"{}\":", pool.name), |a, alias| {
+                match config.find_pool_map(alias) {
+                    Some(map) => format!("{}\n{}", a, config.format_pool_map(map)),
+                    None => format!("{}\n{} (not in the map pool)", a, alias)
+                }
+            }))
+        }
+        "schedule" => handle_schedule(arguments, config),
+        x => invalid_argument(x, HELP_POOL_ARGUMENT)
+    }
+}
+
+/// `-pool schedule add HH:MM (weekday) pool`, `-pool schedule remove X` and `-pool schedule list`.
+fn handle_schedule(arguments: &Vec<&str>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 2, HELP_POOL_SCHEDULE)?.to_lowercase().as_str() {
+        "add" => {
+            let time = pa(arguments, 3, HELP_POOL_TIME)?;
+            parse_time(time).ok_or_else(|| {
+                IvanError { input: format!("\"{}\" {}", time, HELP_POOL_TIME), kind: BotErrorKind::InvalidArgument }
+            })?;
+            let (weekday, pool) = match arguments.get(5) {
+                Some(pool) => {
+                    let day = arguments[4].to_lowercase();
+                    if !WEEKDAYS.contains(&day.as_str()) {
+                        return Err(IvanError { input: format!("\"{}\" {}", day, HELP_POOL_TIME), kind: BotErrorKind::InvalidArgument });
+                    }
+                    (Some(day), pool.to_string())
+                }
+                None => (None, pa(arguments, 4, HELP_POOL_NAME)?.to_string())
+            };
+            config.add_schedule(PoolSchedule { time: time.to_string(), weekday, pool: pool.clone() })?;
+            Ok(format!("Pool \"{}\" will be used from {} UTC", pool, time))
+        }
+        "remove" => {
+            let index: usize = parse_number(pa(arguments, 3, HELP_POOL_SCHEDULE)?)?;
+            config.remove_schedule(index)?;
+            Ok(format!("Removed schedule {}", index))
+        }
+        "list" => {
+            if config.get_schedules().is_empty() {
+                return Ok("There are no pool schedules".to_string());
+            }
+            Ok(config.get_schedules().iter().enumerate().fold("Pool schedules (UTC):".to_string(), |a, (index, schedule)| {
+                format!("{}\n{}: {} {}-> {}", a, index + 1, schedule.time, schedule.weekday.as_ref().map(|day| format!("{} ", day)).unwrap_or_default(), schedule.pool)
+            }))
+        }
+        x => invalid_argument(x, HELP_POOL_SCHEDULE)
+    }
+}
+
+pub fn unknown_pool(name: &str) -> IvanError {
+    IvanError { input: format!("there is no pool \"{}\", use -pool list", name), kind: BotErrorKind::InvalidArgument }
+}
+
+/// Parses HH:MM into the minute of the day.
+fn parse_time(time: &str) -> Option<u64> {
+    let parts: Vec<&str> = time.splitn(2, ':').collect();
+    match parts.as_slice() {
+        [hours, minutes] if minutes.len() == 2 => {
+            let hours: u64 = hours.parse().ok()?;
+            let minutes: u64 = minutes.parse().ok()?;
+            if hours < 24 && minutes < 60 { Some(hours * 60 + minutes) } else { None }
+        }
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(time: &str, weekday: Option<&str>, pool: &str) -> PoolSchedule {
+        PoolSchedule { time: time.to_string(), weekday: weekday.map(|day| day.to_string()), pool: pool.to_string() }
+    }
+
+    #[test]
+    fn times_are_parsed_as_minute_of_day() {
+        assert_eq!(parse_time("00:00"), Some(0));
+        assert_eq!(parse_time("20:30"), Some(20 * 60 + 30));
+        assert_eq!(parse_time("23:59"), Some(23 * 60 + 59));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("12:60"), None);
+        assert_eq!(parse_time("12:5"), None);
+        assert_eq!(parse_time("noon"), None);
+    }
+
+    #[test]
+    fn daily_schedules_are_due_every_day() {
+        let schedules = vec![schedule("20:00", None, "evening")];
+        assert_eq!(scheduled_pool(&schedules, 20 * 60), Some("evening".to_string()));
+        assert_eq!(scheduled_pool(&schedules, 5 * 24 * 60 + 20 * 60), Some("evening".to_string()));
+        assert_eq!(scheduled_pool(&schedules, 20 * 60 + 1), None);
+    }
+
+    #[test]
+    fn weekday_schedules_are_only_due_on_that_day() {
+        let schedules = vec![schedule("18:00", Some("fri"), "scrim")];
+        // 1970-01-01 was a thursday, so day 1 is a friday
+        assert_eq!(scheduled_pool(&schedules, 24 * 60 + 18 * 60), Some("scrim".to_string()));
+        assert_eq!(scheduled_pool(&schedules, 18 * 60), None);
+        assert_eq!(scheduled_pool(&schedules, 8 * 24 * 60 + 18 * 60), Some("scrim".to_string()));
+    }
+
+    #[test]
+    fn minutes_skipped_by_a_late_check_are_caught_up() {
+        let schedules = vec![schedule("20:00", None, "evening"), schedule("20:01", None, "late")];
+        assert_eq!(due_pool(&schedules, 20 * 60 - 1, 20 * 60 + 2), Some("late".to_string()));
+        assert_eq!(due_pool(&schedules, 20 * 60 - 1, 20 * 60), Some("evening".to_string()));
+        assert_eq!(due_pool(&schedules, 20 * 60 + 1, 20 * 60 + 3), None);
+    }
+}
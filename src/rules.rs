@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::{Duration, Instant};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::audit::audit;
+use crate::background::announce;
+use crate::config::PlayerInfo;
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument};
+use crate::pavlov::{PavlovCommands, TeamId, pa, parse_number};
+use crate::votekick::{TempBan, now};
+use crate::help::{HELP_RULES, HELP_RULE_TYPE, HELP_RULE_VALUE, HELP_RULE_ACTION, HELP_VOTEKICK_SETTING};
+
+/// The KDA rule ignores players with fewer kills, a lucky start is not implausible.
+const KDA_MIN_KILLS: u64 = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RuleSettings {
+    /// Only report what the rules would do.
+    pub dry_run: bool,
+    /// Minutes a player stays banned by a ban rule.
+    pub ban_duration: u64,
+    pub rules: Vec<Rule>,
+}
+
+impl Default for RuleSettings {
+    fn default() -> Self {
+        RuleSettings { dry_run: true, ban_duration: 60, rules: vec![] }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rule {
+    pub condition: RuleCondition,
+    pub action: RuleAction,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RuleCondition {
+    Name { pattern: String },
+    Kda { max: f64 },
+    Score { max: i64 },
+    Afk { minutes: u64 },
+    /// The bigger team has more than `players` players more than the other team.
+    Imbalance { players: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Warn,
+    Kick,
+    Ban,
+    Switch,
+}
+
+impl fmt::Display for RuleCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleCondition::Name { pattern } => write!(f, "name matches {}", pattern),
+            RuleCondition::Kda { max } => write!(f, "kills per death above {}", max),
+            RuleCondition::Score { max } => write!(f, "score above {}", max),
+            RuleCondition::Afk { minutes } => write!(f, "same score for {} minutes", minutes),
+            RuleCondition::Imbalance { players } => write!(f, "teams differ by more than {} players", players)
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            RuleAction::Warn => "warn",
+            RuleAction::Kick => "kick",
+            RuleAction::Ban => "ban",
+            RuleAction::Switch => "switch"
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.condition, self.action)
+    }
+}
+
+/// What the background thread remembers between rule checks.
+#[derive(Default)]
+pub struct RulesState {
+    /// Score of every player and since when it hasn't changed.
+    afk: HashMap<u64, (String, Instant)>,
+    /// Rules (with the steam id, 0 for team rules) that already acted, they act again once the
+    /// condition was over in between.
+    triggered: HashSet<(String, u64)>,
+    /// Name patterns compiled once, by pattern.
+    regexes: HashMap<String, Regex>,
+}
+
+/// `-rules list`, `-rules add type value action`, `-rules remove X`, `-rules dryrun [on, off]`
+/// and `-rules ban minutes`.
+pub fn handle_rules(arguments: &Vec<&str>, framework: &mut CustomFramework, author: u64) -> Result<String, IvanError> {
+    let mut settings = framework.config.get_rules().clone();
+    let response = match pa(arguments, 1, HELP_RULES)?.to_lowercase().as_str() {
+        "list" => {
+            let mode = if settings.dry_run { "dry run, rules only report" } else { "rules act" };
+            return Ok(settings.rules.iter().enumerate().fold(format!("Rules ({}):", mode), |a, (index, rule)| {
+                format!("{}\n{}: {}", a, index + 1, rule)
+            }));
+        }
+        "add" => {
+            let rule = parse_rule(arguments)?;
+            let response = format!("Added rule {}", rule);
+            settings.rules.push(rule);
+            response
+        }
+        "remove" => {
+            let index: usize = parse_number(pa(arguments, 2, HELP_RULES)?)?;
+            if index == 0 || index > settings.rules.len() {
+                return Err(IvanError { input: format!("there is no rule {}, use -rules list", index), kind: BotErrorKind::InvalidArgument });
+            }
+            let rule = settings.rules.remove(index - 1);
+            format!("Removed rule {}", rule)
+        }
+        "dryrun" => {
+            settings.dry_run = match pa(arguments, 2, HELP_RULES)?.to_lowercase().as_str() {
+                "on" => true,
+                "off" => false,
+                x => return invalid_argument(x, HELP_RULES)
+            };
+            if settings.dry_run { "Rules only report what they would do".to_string() } else { "Rules act on players".to_string() }
+        }
+        "ban" => {
+            settings.ban_duration = parse_number(pa(arguments, 2, HELP_VOTEKICK_SETTING)?)?;
+            format!("Ban rules ban for {} minutes", settings.ban_duration)
+        }
+        x => return invalid_argument(x, HELP_RULES)
+    };
+    framework.config.set_rules(settings)?;
+    audit(author, response.clone());
+    Ok(response)
+}
+
+fn parse_rule(arguments: &Vec<&str>) -> Result<Rule, IvanError> {
+    let kind = pa(arguments, 2, HELP_RULE_TYPE)?.to_lowercase();
+    let value = pa(arguments, 3, HELP_RULE_VALUE)?;
+    let condition = match kind.as_str() {
+        "name" => {
+            Regex::new(value).map_err(|err| IvanError { input: format!("\"{}\" is not a valid regex: {}", value, err), kind: BotErrorKind::InvalidArgument })?;
+            RuleCondition::Name { pattern: value.to_string() }
+        }
+        "kda" => RuleCondition::Kda { max: parse_number(value)? },
+        "score" => RuleCondition::Score { max: parse_number(value)? },
+        "afk" => RuleCondition::Afk { minutes: parse_number(value)? },
+        "imbalance" => RuleCondition::Imbalance { players: parse_number(value)? },
+        x => return Err(IvanError { input: format!("\"{}\" {}", x, HELP_RULE_TYPE), kind: BotErrorKind::InvalidArgument })
+    };
+    let action = match pa(arguments, 4, HELP_RULE_ACTION)?.to_lowercase().as_str() {
+        "warn" => RuleAction::Warn,
+        "kick" => RuleAction::Kick,
+        "ban" => RuleAction::Ban,
+        "switch" => RuleAction::Switch,
+        x => return Err(IvanError { input: format!("\"{}\" {}", x, HELP_RULE_ACTION), kind: BotErrorKind::InvalidArgument })
+    };
+    if let RuleCondition::Imbalance { .. } = condition {
+        if action == RuleAction::Kick || action == RuleAction::Ban {
+            return Err(IvanError { input: format!("an imbalance rule can only warn or switch, \"{}\" {}", action, HELP_RULE_ACTION), kind: BotErrorKind::InvalidArgument });
+        }
+    }
+    Ok(Rule { condition, action })
+}
+
+/// Evaluates all rules against the inspected players on the server.
+pub fn run_rules(framework: &mut CustomFramework, http: &Http, players: &[PlayerInfo], state: &mut RulesState) {
+    let settings = framework.config.get_rules().clone();
+    track_scores(players, state);
+    compile_patterns(&settings.rules, state);
+    let mut active: HashSet<(String, u64)> = HashSet::new();
+    for rule in &settings.rules {
+        if let RuleCondition::Imbalance { players: allowed } = rule.condition {
+            if imbalance(players) > allowed {
+                let key = (rule.to_string(), 0);
+                if !state.triggered.contains(&key) {
+                    apply_team_rule(framework, http, rule, players, &settings);
+                }
+                active.insert(key);
+            }
+            continue;
+        }
+        let regex = match &rule.condition {
+            RuleCondition::Name { pattern } => state.regexes.get(pattern),
+            _ => None
+        };
+        for player in players {
+            let steam_id: u64 = match player.UniqueId.parse() {
+                Ok(value) => value,
+                Err(_) => continue
+            };
+            // staff can only be recognized once they linked their steam account
+            let exempt = framework.config.is_staff_player(steam_id) || framework.config.get_votekick().immune.contains(&steam_id);
+            if exempt || !matches(&rule.condition, regex, player, state) {
+                continue;
+            }
+            let key = (rule.to_string(), steam_id);
+            if !state.triggered.contains(&key) {
+                apply_player_rule(framework, http, rule, player, steam_id, &settings);
+            }
+            active.insert(key);
+        }
+    }
+    state.triggered = active;
+}
+
+/// Compiles the name patterns that are new since the last check and forgets removed ones.
+fn compile_patterns(rules: &[Rule], state: &mut RulesState) {
+    let patterns: Vec<&String> = rules.iter().filter_map(|rule| match &rule.condition {
+        RuleCondition::Name { pattern } => Some(pattern),
+        _ => None
+    }).collect();
+    state.regexes.retain(|pattern, _| patterns.contains(&pattern));
+    for pattern in patterns {
+        if !state.regexes.contains_key(pattern) {
+            match Regex::new(pattern) {
+                Ok(regex) => { state.regexes.insert(pattern.clone(), regex); }
+                Err(err) => println!("skipping the invalid rule pattern {}: {}", pattern, err)
+            }
+        }
+    }
+}
+
+fn track_scores(players: &[PlayerInfo], state: &mut RulesState) {
+    state.afk.retain(|steam_id, _| players.iter().any(|player| player.UniqueId == steam_id.to_string()));
+    for player in players {
+        let steam_id: u64 = match player.UniqueId.parse() {
+            Ok(value) => value,
+            Err(_) => continue
+        };
+        match state.afk.get(&steam_id) {
+            Some((score, _)) if *score == player.Score => {}
+            _ => { state.afk.insert(steam_id, (player.Score.clone(), Instant::now())); }
+        }
+    }
+}
+
+fn matches(condition: &RuleCondition, regex: Option<&Regex>, player: &PlayerInfo, state: &RulesState) -> bool {
+    match condition {
+        RuleCondition::Name { .. } => regex.map(|regex| regex.is_match(&player.PlayerName)).unwrap_or(false),
+        RuleCondition::Kda { max } => {
+            let values: Vec<u64> = player.KDA.split('/').filter_map(|value| value.trim().parse().ok()).collect();
+            match values.as_slice() {
+                [kills, deaths, ..] => *kills >= KDA_MIN_KILLS && *kills as f64 / std::cmp::max(*deaths, 1) as f64 > *max,
+                _ => false
+            }
+        }
+        RuleCondition::Score { max } => player.Score.trim().parse::<i64>().map(|score| score > *max).unwrap_or(false),
+        RuleCondition::Afk { minutes } => {
+            let steam_id: u64 = player.UniqueId.parse().unwrap_or(0);
+            state.afk.get(&steam_id).map(|(_, since)| since.elapsed() >= Duration::from_secs(minutes * 60)).unwrap_or(false)
+        }
+        RuleCondition::Imbalance { .. } => false
+    }
+}
+
+pub(crate) fn team_of(player: &PlayerInfo) -> TeamId {
+    player.TeamId.trim().parse().unwrap_or(0)
+}
+
+/// How many players the bigger team has more than the smaller one.
+pub(crate) fn imbalance(players: &[PlayerInfo]) -> u64 {
+    let red = players.iter().filter(|player| team_of(player) == 1).count() as i64;
+    let blue = players.len() as i64 - red;
+    (red - blue).unsigned_abs()
+}
+
+fn apply_player_rule(framework: &mut CustomFramework, http: &Http, rule: &Rule, player: &PlayerInfo, steam_id: u64, settings: &RuleSettings) {
+    if settings.dry_run && rule.action != RuleAction::Warn {
+        announce(http, &framework.config, format!("Dry run, rule \"{}\" would {} {} ({})", rule, rule.action, player.PlayerName, steam_id));
+        return;
+    }
+    let reason = format!("rule \"{}\"", rule);
+    match rule.action {
+        RuleAction::Warn => {}
+        RuleAction::Kick => { framework.connection.execute_command(PavlovCommands::Kick(steam_id)); }
+        RuleAction::Ban => {
+            framework.connection.execute_command(PavlovCommands::Ban(steam_id));
+            let ban = TempBan { steam_id, name: player.PlayerName.clone(), reason: reason.clone(), until: now() + settings.ban_duration * 60 };
+            framework.config.add_temp_ban(ban).unwrap_or_else(|err| {
+                println!("could not record the temporary ban of {}, it has to be lifted by hand: {}", steam_id, err);
+            });
+        }
+        RuleAction::Switch => { framework.connection.execute_command(PavlovCommands::SwitchTeam(steam_id, 1 - team_of(player).min(1))); }
+    }
+    if rule.action != RuleAction::Warn {
+        audit(0, format!("{} {} ({}) because of {}", rule.action, player.PlayerName, steam_id, reason));
+    }
+    announce(http, &framework.config, format!("{} ({}) broke {}, action: {}", player.PlayerName, steam_id, reason, rule.action));
+}
+
+/// Warns about uneven teams or moves the lowest scoring players of the bigger team.
+fn apply_team_rule(framework: &mut CustomFramework, http: &Http, rule: &Rule, players: &[PlayerInfo], settings: &RuleSettings) {
+    let difference = imbalance(players);
+    if rule.action == RuleAction::Warn {
+        announce(http, &framework.config, format!("The teams differ by {} players, rule \"{}\"", difference, rule));
+        return;
+    }
+    let red = players.iter().filter(|player| team_of(player) == 1).count() as u64;
+    let bigger: TeamId = if red * 2 > players.len() as u64 { 1 } else { 0 };
+    let mut candidates: Vec<&PlayerInfo> = players.iter().filter(|player| team_of(player) == bigger).collect();
+    candidates.sort_by_key(|player| player.Score.trim().parse::<i64>().unwrap_or(0));
+    let moved: Vec<&PlayerInfo> = candidates.into_iter().take((difference / 2) as usize).collect();
+    let names = moved.iter().map(|player| player.PlayerName.clone()).collect::<Vec<String>>().join(", ");
+    if settings.dry_run {
+        announce(http, &framework.config, format!("Dry run, rule \"{}\" would move {} to team {}", rule, names, 1 - bigger));
+        return;
+    }
+    for player in &moved {
+        if let Ok(steam_id) = player.UniqueId.parse() {
+            framework.connection.execute_command(PavlovCommands::SwitchTeam(steam_id, 1 - bigger));
+        }
+    }
+    audit(0, format!("moved {} to team {} because of rule \"{}\"", names, 1 - bigger, rule));
+    announce(http, &framework.config, format!("The teams differ by {} players, moved {} to team {}", difference, names, 1 - bigger));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn player(name: &str, kda: &str, score: &str, team: &str) -> PlayerInfo {
+        PlayerInfo { PlayerName: name.to_string(), UniqueId: "76561190000000001".to_string(), KDA: kda.to_string(), Score: score.to_string(), Cash: "0".to_string(), TeamId: team.to_string() }
+    }
+
+    #[test]
+    fn name_rules_use_the_compiled_pattern() {
+        let mut state = RulesState::default();
+        let rules = vec![Rule { condition: RuleCondition::Name { pattern: "(?i)admin".to_string() }, action: RuleAction::Kick }];
+        compile_patterns(&rules, &mut state);
+        let regex = state.regexes.get("(?i)admin");
+        assert!(matches(&rules[0].condition, regex, &player("TheAdmin", "0/0/0", "0", "0"), &state));
+        assert!(!matches(&rules[0].condition, regex, &player("Ivan", "0/0/0", "0", "0"), &state));
+        compile_patterns(&[], &mut state);
+        assert!(state.regexes.is_empty());
+    }
+
+    #[test]
+    fn kda_rules_need_enough_kills() {
+        let state = RulesState::default();
+        let condition = RuleCondition::Kda { max: 4.0 };
+        assert!(matches(&condition, None, &player("Ivan", "25/5/0", "0", "0"), &state));
+        assert!(!matches(&condition, None, &player("Ivan", "20/5/0", "0", "0"), &state));
+        assert!(!matches(&condition, None, &player("Ivan", "9/0/0", "0", "0"), &state));
+        assert!(matches(&condition, None, &player("Ivan", "10/0/0", "0", "0"), &state));
+        assert!(!matches(&condition, None, &player("Ivan", "", "0", "0"), &state));
+    }
+
+    #[test]
+    fn score_rules_compare_the_score() {
+        let state = RulesState::default();
+        let condition = RuleCondition::Score { max: 100 };
+        assert!(matches(&condition, None, &player("Ivan", "0/0/0", "101", "0"), &state));
+        assert!(!matches(&condition, None, &player("Ivan", "0/0/0", "100", "0"), &state));
+    }
+
+    #[test]
+    fn imbalance_is_the_difference_between_the_teams() {
+        assert_eq!(imbalance(&[]), 0);
+        let players = vec![player("a", "", "0", "1"), player("b", "", "0", "1"), player("c", "", "0", "1"), player("d", "", "0", "0")];
+        assert_eq!(imbalance(&players), 2);
+        let players = vec![player("a", "", "0", "0"), player("b", "", "0", "0"), player("c", "", "0", "0"), player("d", "", "0", "1")];
+        assert_eq!(imbalance(&players), 2);
+        let players = vec![player("a", "", "0", "0"), player("b", "", "0", "1")];
+        assert_eq!(imbalance(&players), 0);
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use crate::config::IvanConfig;
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, BotErrorKind, invalid_argument, get_player_list, inspect_all};
+use crate::pavlov::{PavlovCommands, GameMode, Skin, TeamId, pa, parse_number, parse_skin, parse_game_mode};
+use crate::help::{HELP_SKIN, HELP_SKIN_THEME, HELP_SKIN_THEME_NAME, HELP_VALID_TEAM};
+
+/// A skin for each team, team 0 is blue and team 1 is red.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SkinTheme {
+    pub name: String,
+    pub blue: Skin,
+    pub red: Skin,
+}
+
+/// The theme applied after votes on a game mode.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModeTheme {
+    pub gamemode: GameMode,
+    pub theme: String,
+}
+
+/// Themes that come with the bot, a stored theme with the same name replaces one of them.
+pub fn bundled_themes() -> Vec<SkinTheme> {
+    vec![
+        SkinTheme { name: "ww2".to_string(), blue: Skin::German, red: Skin::Soviet },
+        SkinTheme { name: "modern".to_string(), blue: Skin::Nato, red: Skin::Russian },
+        SkinTheme { name: "coldwar".to_string(), blue: Skin::Us, red: Skin::Soviet },
+        SkinTheme { name: "heist".to_string(), blue: Skin::Cop, red: Skin::Prisoner },
+    ]
+}
+
+/// `-skin theme name`, `-skin theme list`, `-skin theme add name skin skin`, `-skin theme remove name`
+/// and `-skin theme mode gamemode {name/none}`.
+pub fn handle_skin_theme(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let first = pa(arguments, 2, HELP_SKIN_THEME)?;
+    match first.to_lowercase().as_str() {
+        "list" => {
+            let config = &framework.config;
+            Ok(config.get_skin_themes().iter().fold("Skin themes (team 0 blue, team 1 red):".to_string(), |a, theme| {
+                let modes: Vec<String> = config.get_mode_themes().iter().filter(|value| value.theme.eq_ignore_ascii_case(&theme.name)).map(|value| value.gamemode.to_string()).collect();
+                let after = if modes.is_empty() { "".to_string() } else { format!(" \tafter votes on {}", modes.join(", ")) };
+                format!("{}\n{}: {} vs {}{}", a, theme.name, theme.blue, theme.red, after)
+            }))
+        }
+        "add" => {
+            let name = pa(arguments, 3, HELP_SKIN_THEME_NAME)?;
+            if ["list", "add", "remove", "mode"].contains(&name.to_lowercase().as_str()) {
+                return invalid_argument(name, HELP_SKIN_THEME_NAME);
+            }
+            let blue = parse_skin(pa(arguments, 4, HELP_SKIN)?)?;
+            let red = parse_skin(pa(arguments, 5, HELP_SKIN)?)?;
+            framework.config.save_skin_theme(SkinTheme { name: name.to_lowercase(), blue, red })?;
+            Ok(format!("Saved skin theme {}: team 0 {} and team 1 {}", name.to_lowercase(), blue, red))
+        }
+        "remove" => {
+            let name = pa(arguments, 3, HELP_SKIN_THEME_NAME)?;
+            framework.config.remove_skin_theme(name)?;
+            Ok(format!("Removed skin theme {}", name))
+        }
+        "mode" => {
+            let gamemode = parse_game_mode(pa(arguments, 3, &framework.config.get_catalog().help_game_modes())?, &framework.config)?;
+            let name = pa(arguments, 4, HELP_SKIN_THEME_NAME)?;
+            if name.eq_ignore_ascii_case("none") {
+                framework.config.set_mode_theme(gamemode.clone(), None)?;
+                return Ok(format!("Votes on {} no longer apply a skin theme", gamemode));
+            }
+            let theme = find_theme(&framework.config, name)?.name;
+            framework.config.set_mode_theme(gamemode.clone(), Some(theme.clone()))?;
+            Ok(format!("Votes on {} apply skin theme {}", gamemode, theme))
+        }
+        name => {
+            let theme = find_theme(&framework.config, name)?;
+            assign_theme(framework, &theme)
+        }
+    }
+}
+
+/// `-skin team <0|1> {skin/random}` changes the skin of one team.
+pub fn handle_skin_team(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let team: TeamId = parse_number(pa(arguments, 2, HELP_VALID_TEAM)?)?;
+    if team > 1 {
+        return invalid_argument(&team.to_string(), HELP_VALID_TEAM);
+    }
+    let value = pa(arguments, 3, HELP_SKIN)?;
+    let skin = if value.eq_ignore_ascii_case("random") { None } else { Some(parse_skin(value)?) };
+    assign_team_skins(framework, |player_team| {
+        if player_team == team { Some(skin.unwrap_or_else(Skin::get_random)) } else { None }
+    })
+}
+
+fn find_theme(config: &IvanConfig, name: &str) -> Result<SkinTheme, IvanError> {
+    config.find_skin_theme(name).ok_or_else(|| {
+        IvanError { input: format!("there is no skin theme \"{}\", use -skin theme list", name), kind: BotErrorKind::InvalidArgument }
+    })
+}
+
+pub fn assign_theme(framework: &mut CustomFramework, theme: &SkinTheme) -> Result<String, IvanError> {
+    let (blue, red) = (theme.blue, theme.red);
+    assign_team_skins(framework, |team| Some(if team == 1 { red } else { blue }))
+}
+
+/// Gives every player the skin `skin_decider` picks for their team, players it returns none for keep their skin.
+fn assign_team_skins<F: Fn(TeamId) -> Option<Skin>>(framework: &mut CustomFramework, skin_decider: F) -> Result<String, IvanError> {
+    let players = inspect_all(get_player_list(framework)?, framework)?;
+    if players.is_empty() {
+        return Ok("Could not assign skins because there are no players on the server".to_string());
+    }
+    let mut msg = String::from("\n");
+    for player in players {
+        let team: TeamId = player.TeamId.trim().parse().unwrap_or(0);
+        if let Some(skin) = skin_decider(team) {
+            msg = format!("{}Player: \"{}\" gets the skin: \"{}\"\n", msg, player.PlayerName, skin);
+            println!("{}", framework.connection.execute_command(PavlovCommands::SetPlayerSkin(parse_number(player.UniqueId.as_str())?, skin)));
+        }
+    }
+    Ok(msg)
+}
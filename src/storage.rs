@@ -0,0 +1,399 @@
+use std::env::var;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use lazy_static::lazy_static;
+use rusqlite::{Connection, params, Transaction};
+use serde_json::{Value, Map, to_value, from_value, from_str};
+use crate::backup::{backup_config, write_atomic};
+use crate::config::{IvanConfig, get_path, get_data_path, read_config_file, serialize_config};
+use crate::matches::MatchRecord;
+use crate::migration::migrate;
+use crate::model::{IvanError, BotErrorKind};
+
+const IVAN_DATABASE: &str = "ivan.db";
+const IVAN_MATCHES: &str = "ivan_matches.json";
+/// The json file only keeps this many matches, the database keeps all of them.
+const JSON_MATCH_LIMIT: usize = 500;
+
+/// Where the config is persisted. `IvanConfig` stays the in-memory model, a storage only decides
+/// how its admins, mods, aliases, maps and settings end up on disk.
+pub trait Storage: Send + Sync {
+    fn exists(&self) -> bool;
+    fn load(&self) -> Result<IvanConfig, IvanError>;
+    fn save(&self, config: &IvanConfig) -> Result<(), IvanError>;
+    /// Last modification of the underlying file, used to notice edits made outside the bot.
+    fn modified(&self) -> Option<SystemTime>;
+    fn add_match(&self, record: &MatchRecord) -> Result<(), IvanError>;
+    /// The last `count` finished matches, newest first.
+    fn recent_matches(&self, count: usize) -> Result<Vec<MatchRecord>, IvanError>;
+}
+
+/// The ivan.json file, with atomic writes and backups.
+pub struct JsonStorage;
+
+/// An embedded SQLite database, selected with IVAN_STORAGE=sqlite.
+pub struct SqliteStorage {
+    path: String,
+    state: Mutex<SqliteState>,
+}
+
+/// The connection is opened once and kept. `saved` is the config as it is in the database, saves
+/// only write the admins, mods, aliases, maps and settings that differ from it.
+#[derive(Default)]
+struct SqliteState {
+    connection: Option<Connection>,
+    saved: Map<String, Value>,
+}
+
+lazy_static! {
+    /// The storage selected with IVAN_STORAGE (json or sqlite, default json), created on first use.
+    static ref STORAGE: Box<dyn Storage> = match var("IVAN_STORAGE").unwrap_or_default().to_lowercase().as_str() {
+        "sqlite" => Box::new(SqliteStorage {
+            path: var("IVAN_DATABASE").unwrap_or_else(|_| get_data_path(IVAN_DATABASE)),
+            state: Mutex::new(SqliteState::default()),
+        }),
+        _ => Box::new(JsonStorage)
+    };
+}
+
+pub fn get_storage() -> &'static dyn Storage {
+    STORAGE.as_ref()
+}
+
+impl Storage for JsonStorage {
+    fn exists(&self) -> bool {
+        Path::new(&get_path()).exists()
+    }
+
+    fn load(&self) -> Result<IvanConfig, IvanError> {
+        let (config, migrated_from, file) = read_config_file(&get_path())?;
+        if let Some(version) = migrated_from {
+            let backup = format!("{}.v{}.bak", get_path(), version);
+            fs::write(&backup, &file).map_err(|err| {
+                IvanError { input: format!("could not back up the config to {} before migrating it: {}", backup, err), kind: BotErrorKind::WriteError }
+            })?;
+            println!("backed up the version {} config to {}", version, backup);
+            self.save(&config)?;
+        }
+        Ok(config)
+    }
+
+    fn save(&self, config: &IvanConfig) -> Result<(), IvanError> {
+        let values = serialize_config(config)?;
+        if let Ok(previous) = fs::read_to_string(get_path()) {
+            backup_config(&previous)?;
+        }
+        write_atomic(&get_path(), &values)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(get_path()).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    fn add_match(&self, record: &MatchRecord) -> Result<(), IvanError> {
+        let mut matches = read_json_matches()?;
+        matches.push(record.clone());
+        let skip = matches.len().saturating_sub(JSON_MATCH_LIMIT);
+        let values = serde_json::to_string(&matches[skip..]).map_err(|err| {
+            IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
+        })?;
+        write_atomic(&get_data_path(IVAN_MATCHES), &values)
+    }
+
+    fn recent_matches(&self, count: usize) -> Result<Vec<MatchRecord>, IvanError> {
+        Ok(read_json_matches()?.into_iter().rev().take(count).collect())
+    }
+}
+
+fn read_json_matches() -> Result<Vec<MatchRecord>, IvanError> {
+    let path = get_data_path(IVAN_MATCHES);
+    if !Path::new(&path).exists() {
+        return Ok(vec![]);
+    }
+    let file = fs::read_to_string(&path).map_err(|err| {
+        IvanError { input: format!("{}: {}", path, err), kind: BotErrorKind::StorageError }
+    })?;
+    from_str(&file).map_err(deserialize_error)
+}
+
+/// The lists get their own tables, every other config key is a row in `settings` holding json.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS admins (id INTEGER PRIMARY KEY);
+CREATE TABLE IF NOT EXISTS mods (id INTEGER PRIMARY KEY);
+CREATE TABLE IF NOT EXISTS aliases (position INTEGER PRIMARY KEY, alias TEXT NOT NULL, map TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS maps (position INTEGER PRIMARY KEY, map TEXT NOT NULL, gamemode TEXT NOT NULL, alias TEXT NOT NULL, extra TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS matches (id INTEGER PRIMARY KEY AUTOINCREMENT, start INTEGER NOT NULL, end INTEGER NOT NULL, map TEXT NOT NULL, gamemode TEXT NOT NULL, record TEXT NOT NULL);
+";
+
+const TABLE_KEYS: [&str; 4] = ["admins", "mods", "aliases", "maps"];
+
+impl SqliteStorage {
+    /// Runs `query` with the kept connection, it is opened and the schema is created on first use.
+    fn with_connection<T, F>(&self, query: F) -> Result<T, IvanError>
+        where F: FnOnce(&mut Connection, &mut Map<String, Value>) -> Result<T, IvanError> {
+        let mut guard = self.state.lock().map_err(|_| {
+            IvanError { input: format!("a write to {} panicked earlier", self.path), kind: BotErrorKind::StorageError }
+        })?;
+        let state = &mut *guard;
+        if state.connection.is_none() {
+            let connection = Connection::open(&self.path).map_err(storage_error)?;
+            connection.execute_batch(SCHEMA).map_err(storage_error)?;
+            state.connection = Some(connection);
+        }
+        match state.connection.as_mut() {
+            Some(connection) => query(connection, &mut state.saved),
+            None => Err(IvanError { input: format!("could not open {}", self.path), kind: BotErrorKind::StorageError })
+        }
+    }
+
+    /// Moves an existing ivan.json into the empty database, once.
+    fn import_json(&self) -> Result<IvanConfig, IvanError> {
+        let config = JsonStorage.load()?;
+        self.save(&config)?;
+        let migrated = format!("{}.migrated", get_path());
+        fs::rename(get_path(), &migrated).map_err(|err| {
+            IvanError { input: format!("moved the config into {} but could not rename {}: {}", self.path, get_path(), err), kind: BotErrorKind::StorageError }
+        })?;
+        println!("moved {} into {}, the old file is kept as {}", get_path(), self.path, migrated);
+        Ok(config)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn exists(&self) -> bool {
+        let stored = Path::new(&self.path).exists() && self.with_connection(|connection, _| is_empty(connection)).map(|empty| !empty).unwrap_or(true);
+        stored || JsonStorage.exists()
+    }
+
+    fn load(&self) -> Result<IvanConfig, IvanError> {
+        if self.with_connection(|connection, _| is_empty(connection))? {
+            if JsonStorage.exists() {
+                return self.import_json();
+            }
+            return Err(IvanError { input: format!("{} holds no config yet", self.path), kind: BotErrorKind::ReadConfigError });
+        }
+        let object = self.with_connection(|connection, saved| {
+            let object = read_object(connection)?;
+            *saved = object.clone();
+            Ok(object)
+        })?;
+        let (value, migrated_from) = migrate(Value::Object(object.clone()))?;
+        let config = from_value::<IvanConfig>(value).map_err(deserialize_error)?.with_merged_catalog();
+        if let Some(version) = migrated_from {
+            let backup = format!("{}.v{}.bak", self.path, version);
+            fs::write(&backup, serialize_object(&object)?).map_err(|err| {
+                IvanError { input: format!("could not back up the config to {} before migrating it: {}", backup, err), kind: BotErrorKind::WriteError }
+            })?;
+            println!("backed up the version {} config to {}", version, backup);
+            self.save(&config)?;
+        }
+        Ok(config)
+    }
+
+    fn save(&self, config: &IvanConfig) -> Result<(), IvanError> {
+        let object = config_object(config)?;
+        self.with_connection(|connection, saved| {
+            if !saved.is_empty() {
+                backup_config(&serialize_object(saved)?)?;
+            }
+            let transaction = connection.transaction().map_err(storage_error)?;
+            for (key, value) in object.iter().filter(|(key, value)| saved.get(key.as_str()) != Some(value)) {
+                write_entry(&transaction, key, value)?;
+            }
+            for key in saved.keys().filter(|key| !object.contains_key(key.as_str()) && !TABLE_KEYS.contains(&key.as_str())) {
+                transaction.execute("DELETE FROM settings WHERE key = ?1", params![key]).map_err(storage_error)?;
+            }
+            transaction.commit().map_err(storage_error)?;
+            *saved = object;
+            Ok(())
+        })
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    fn add_match(&self, record: &MatchRecord) -> Result<(), IvanError> {
+        let value = serde_json::to_string(record).map_err(|err| {
+            IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
+        })?;
+        self.with_connection(|connection, _| {
+            connection.execute("INSERT INTO matches (start, end, map, gamemode, record) VALUES (?1, ?2, ?3, ?4, ?5)", params![
+                record.start as i64, record.end as i64, record.map, record.gamemode, value
+            ]).map_err(storage_error)?;
+            Ok(())
+        })
+    }
+
+    fn recent_matches(&self, count: usize) -> Result<Vec<MatchRecord>, IvanError> {
+        let query = format!("SELECT record FROM matches ORDER BY id DESC LIMIT {}", count);
+        self.with_connection(|connection, _| {
+            select_rows(connection, &query, |row| row.get::<_, String>(0))?.iter().map(|record| {
+                from_str(record).map_err(deserialize_error)
+            }).collect()
+        })
+    }
+}
+
+fn is_empty(connection: &Connection) -> Result<bool, IvanError> {
+    let count: i64 = connection.query_row("SELECT COUNT(*) FROM settings", params![], |row| row.get(0)).map_err(storage_error)?;
+    Ok(count == 0)
+}
+
+/// Reads the tables back into the json object of a config.
+fn read_object(connection: &Connection) -> Result<Map<String, Value>, IvanError> {
+    let mut object = Map::new();
+    object.insert("admins".to_string(), Value::from(select_ids(connection, "SELECT id FROM admins ORDER BY id")?));
+    object.insert("mods".to_string(), Value::from(select_ids(connection, "SELECT id FROM mods ORDER BY id")?));
+    let aliases = select_rows(connection, "SELECT alias, map FROM aliases ORDER BY position", |row| {
+        Ok(Value::from(vec![row.get::<_, String>(0)?, row.get::<_, String>(1)?]))
+    })?;
+    object.insert("aliases".to_string(), Value::from(aliases));
+    let maps = select_rows(connection, "SELECT map, gamemode, alias, extra FROM maps ORDER BY position", |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+    })?.into_iter().map(|(map, gamemode, alias, extra)| {
+        let mut map_object: Map<String, Value> = from_str(&extra).map_err(deserialize_error)?;
+        map_object.insert("map".to_string(), Value::from(map));
+        map_object.insert("gamemode".to_string(), Value::from(gamemode));
+        map_object.insert("alias".to_string(), Value::from(alias));
+        Ok(Value::Object(map_object))
+    }).collect::<Result<Vec<Value>, IvanError>>()?;
+    object.insert("maps".to_string(), Value::from(maps));
+    for (key, value) in select_rows(connection, "SELECT key, value FROM settings", |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })? {
+        object.insert(key, from_str(&value).map_err(deserialize_error)?);
+    }
+    Ok(object)
+}
+
+/// Replaces the stored value of one config key: the rows of its table or its row in `settings`.
+fn write_entry(transaction: &Transaction, key: &str, value: &Value) -> Result<(), IvanError> {
+    match key {
+        "admins" | "mods" => {
+            transaction.execute(&format!("DELETE FROM {}", key), params![]).map_err(storage_error)?;
+            insert_ids(transaction, &format!("INSERT OR IGNORE INTO {} (id) VALUES (?1)", key), Some(value))?;
+        }
+        "aliases" => {
+            transaction.execute("DELETE FROM aliases", params![]).map_err(storage_error)?;
+            for (position, alias) in list(Some(value)).iter().enumerate() {
+                transaction.execute("INSERT INTO aliases (position, alias, map) VALUES (?1, ?2, ?3)", params![
+                    position as i64, text(alias.get(0)), text(alias.get(1))
+                ]).map_err(storage_error)?;
+            }
+        }
+        "maps" => {
+            transaction.execute("DELETE FROM maps", params![]).map_err(storage_error)?;
+            for (position, map) in list(Some(value)).iter().enumerate() {
+                let mut extra = map.as_object().cloned().unwrap_or_default();
+                let (name, gamemode, alias) = (extra.remove("map"), extra.remove("gamemode"), extra.remove("alias"));
+                transaction.execute("INSERT INTO maps (position, map, gamemode, alias, extra) VALUES (?1, ?2, ?3, ?4, ?5)", params![
+                    position as i64, text(name.as_ref()), text(gamemode.as_ref()), text(alias.as_ref()), Value::Object(extra).to_string()
+                ]).map_err(storage_error)?;
+            }
+        }
+        _ => {
+            transaction.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![key, value.to_string()]).map_err(storage_error)?;
+        }
+    }
+    Ok(())
+}
+
+fn config_object(config: &IvanConfig) -> Result<Map<String, Value>, IvanError> {
+    match to_value(config) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(IvanError { input: "config is not an object".to_string(), kind: BotErrorKind::SerializeError }),
+        Err(err) => Err(IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError })
+    }
+}
+
+fn serialize_object(object: &Map<String, Value>) -> Result<String, IvanError> {
+    serde_json::to_string_pretty(object).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
+    })
+}
+
+fn select_rows<T, F>(connection: &Connection, query: &str, map: F) -> Result<Vec<T>, IvanError>
+    where F: FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T> {
+    let mut statement = connection.prepare(query).map_err(storage_error)?;
+    let rows = statement.query_map(params![], map).map_err(storage_error)?;
+    rows.collect::<rusqlite::Result<Vec<T>>>().map_err(storage_error)
+}
+
+fn select_ids(connection: &Connection, query: &str) -> Result<Vec<u64>, IvanError> {
+    Ok(select_rows(connection, query, |row| row.get::<_, i64>(0))?.into_iter().map(|id| id as u64).collect())
+}
+
+fn insert_ids(transaction: &Transaction, query: &str, ids: Option<&Value>) -> Result<(), IvanError> {
+    for id in list(ids).iter().filter_map(|id| id.as_u64()) {
+        transaction.execute(query, params![id as i64]).map_err(storage_error)?;
+    }
+    Ok(())
+}
+
+fn list(value: Option<&Value>) -> Vec<Value> {
+    value.and_then(|value| value.as_array()).cloned().unwrap_or_default()
+}
+
+fn text(value: Option<&Value>) -> String {
+    value.and_then(|value| value.as_str()).unwrap_or("").to_string()
+}
+
+fn storage_error(err: rusqlite::Error) -> IvanError {
+    IvanError { input: err.to_string(), kind: BotErrorKind::StorageError }
+}
+
+fn deserialize_error(err: serde_json::Error) -> IvanError {
+    IvanError { input: err.to_string(), kind: BotErrorKind::DeserializeError }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn database() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        connection
+    }
+
+    fn write_object(connection: &mut Connection, object: &Map<String, Value>) {
+        let transaction = connection.transaction().unwrap();
+        for (key, value) in object {
+            write_entry(&transaction, key, value).unwrap();
+        }
+        transaction.commit().unwrap();
+    }
+
+    #[test]
+    fn config_round_trips_through_the_tables() {
+        let mut object = config_object(&IvanConfig::default()).unwrap();
+        object.insert("admins".to_string(), json!([1, 2]));
+        object.insert("aliases".to_string(), json!([["dust", "SVR_Dust"]]));
+        object.insert("maps".to_string(), json!([{"map": "SVR_Dust", "gamemode": "SND", "alias": "dust", "enabled": false}]));
+        let mut connection = database();
+        write_object(&mut connection, &object);
+        assert!(!is_empty(&connection).unwrap());
+        assert_eq!(read_object(&connection).unwrap(), object);
+    }
+
+    #[test]
+    fn writing_an_entry_replaces_its_rows() {
+        let mut object = config_object(&IvanConfig::default()).unwrap();
+        object.insert("mods".to_string(), json!([5, 6]));
+        let mut connection = database();
+        write_object(&mut connection, &object);
+        let transaction = connection.transaction().unwrap();
+        write_entry(&transaction, "mods", &json!([7])).unwrap();
+        write_entry(&transaction, "skin_shuffle", &json!(true)).unwrap();
+        transaction.commit().unwrap();
+        let stored = read_object(&connection).unwrap();
+        assert_eq!(stored["mods"], json!([7]));
+        assert_eq!(stored["skin_shuffle"], json!(true));
+        assert_eq!(stored["admins"], object["admins"]);
+    }
+}
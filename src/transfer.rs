@@ -0,0 +1,196 @@
+use serde_json::{Value, Map, to_value, from_value, to_string_pretty};
+use crate::config::IvanConfig;
+use crate::migration::{migrate, CONFIG_VERSION};
+use crate::model::{IvanError, BotErrorKind};
+use crate::help::HELP_CONFIG_SECTION;
+
+/// The parts of the config that can be exported and imported, with the config keys in them.
+const SECTIONS: [(&str, &[&str]); 5] = [
+    ("maps", &["maps", "workshop", "economy"]),
+    ("pools", &["pools", "active_pool", "schedules"]),
+    ("aliases", &["aliases"]),
+    ("permissions", &["admins", "mods", "links"]),
+    ("settings", &["channel_lock", "skin_shuffle", "skin_themes", "mode_themes", "gun_mode", "map_vote_count", "team_channels", "lobby_channel", "raw_filter_mode", "raw_filter", "catalog", "log_channel", "votekick", "whitelist", "rules", "autobalance"]),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+/// An import that has been shown as a dry run and waits for `-config import confirm`.
+pub struct PendingImport {
+    before: Value,
+    after: IvanConfig,
+}
+
+impl PendingImport {
+    /// Applies the import unless the config was changed after the dry run.
+    pub fn apply(self, config: &mut IvanConfig) -> Result<(), IvanError> {
+        if config_value(config)? != self.before {
+            return Err(IvanError { input: "the config changed since the dry run, run the import again".to_string(), kind: BotErrorKind::ImportError });
+        }
+        config.replace(self.after)
+    }
+}
+
+/// Parses section names, no names means every section.
+pub fn parse_sections(names: &[&str]) -> Result<Vec<&'static str>, IvanError> {
+    if names.is_empty() {
+        return Ok(SECTIONS.iter().map(|(name, _)| *name).collect());
+    }
+    names.iter().map(|name| {
+        SECTIONS.iter().find(|(section, _)| section.eq_ignore_ascii_case(name)).map(|(section, _)| *section).ok_or_else(|| {
+            IvanError { input: format!("\"{}\" {}", name, HELP_CONFIG_SECTION), kind: BotErrorKind::InvalidArgument }
+        })
+    }).collect()
+}
+
+pub fn export(config: &IvanConfig, sections: &[&str]) -> Result<String, IvanError> {
+    let current = config_value(config)?;
+    let mut export = Map::new();
+    export.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    for key in section_keys(sections) {
+        if let Some(value) = current.get(key) {
+            export.insert(key.to_string(), value.clone());
+        }
+    }
+    to_string_pretty(&export).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
+    })
+}
+
+/// Computes the result of importing `bytes` into `config` without changing it. Returns the
+/// import to apply later and a description of the differences.
+pub fn dry_run(config: &IvanConfig, bytes: &[u8], sections: &[&str], mode: ImportMode) -> Result<(PendingImport, String), IvanError> {
+    let imported: Value = serde_json::from_slice(bytes).map_err(|err| {
+        IvanError { input: format!("the attachment is not valid json: {}", err), kind: BotErrorKind::ImportError }
+    })?;
+    // migrating fills in keys that are missing, so remember which keys the file really had
+    let present: Vec<String> = imported.as_object().map(|object| object.keys().cloned().collect()).unwrap_or_default();
+    let (imported, _) = migrate(imported)?;
+    let before = config_value(config)?;
+    let mut after = before.clone();
+    let mut diff = String::new();
+    for key in section_keys(sections).into_iter().filter(|key| present.iter().any(|value| value == key)) {
+        let current = before.get(key).cloned().unwrap_or(Value::Null);
+        let new = imported.get(key).cloned().unwrap_or(Value::Null);
+        let result = match (mode, current, new) {
+            (ImportMode::Merge, Value::Array(current), Value::Array(new)) => Value::Array(merge_list(key, current, new)),
+            (_, _, new) => new
+        };
+        diff.push_str(describe(key, before.get(key).unwrap_or(&Value::Null), &result).as_str());
+        after.as_object_mut().unwrap().insert(key.to_string(), result);
+    }
+    let after = from_value::<IvanConfig>(after).map_err(|err| {
+        IvanError { input: format!("the imported config is invalid: {}", err), kind: BotErrorKind::ImportError }
+    })?.with_merged_catalog();
+    after.validate().map_err(|err| {
+        IvanError { input: format!("the imported config is invalid: {}", err.input), kind: BotErrorKind::ImportError }
+    })?;
+    if diff.is_empty() {
+        diff = "Nothing would change".to_string();
+    }
+    Ok((PendingImport { before, after }, diff))
+}
+
+fn config_value(config: &IvanConfig) -> Result<Value, IvanError> {
+    to_value(config).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::SerializeError }
+    })
+}
+
+fn section_keys(sections: &[&str]) -> Vec<&'static str> {
+    SECTIONS.iter().filter(|(name, _)| sections.contains(name)).flat_map(|(_, keys)| keys.iter().cloned()).collect()
+}
+
+/// Merges two lists, an imported entry replaces the current entry with the same identity.
+fn merge_list(key: &str, current: Vec<Value>, new: Vec<Value>) -> Vec<Value> {
+    let mut result: Vec<Value> = current.into_iter().filter(|item| {
+        !new.iter().any(|value| identity(key, value) == identity(key, item))
+    }).collect();
+    result.extend(new);
+    result
+}
+
+fn identity(key: &str, item: &Value) -> Value {
+    match key {
+        "maps" => item.get("alias").cloned(),
+        "aliases" => item.get(0).cloned(),
+        "workshop" => item.get("id").cloned(),
+        "pools" | "economy" | "skin_themes" => item.get("name").cloned(),
+        "mode_themes" => item.get("gamemode").cloned(),
+        "links" => item.get("discord").cloned(),
+        _ => None
+    }.map(|value| match value.as_str() {
+        Some(text) => Value::from(text.to_lowercase()),
+        None => value
+    }).unwrap_or_else(|| item.clone())
+}
+
+fn describe(key: &str, before: &Value, after: &Value) -> String {
+    match (before, after) {
+        (Value::Array(before), Value::Array(after)) => {
+            let added = after.iter().filter(|item| !before.contains(item)).fold("".to_string(), |a, item| format!("{}\n+ {}: {}", a, key, item));
+            let removed = before.iter().filter(|item| !after.contains(item)).fold("".to_string(), |a, item| format!("{}\n- {}: {}", a, key, item));
+            format!("{}{}", added, removed)
+        }
+        (before, after) if before != after => format!("\n~ {}: {} -> {}", key, before, after),
+        _ => "".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_replaces_entries_with_the_same_identity() {
+        let current = vec![json!({"alias": "dust", "map": "SVR_Dust"}), json!({"alias": "sand", "map": "sand"})];
+        let new = vec![json!({"alias": "DUST", "map": "UGC1"})];
+        let merged = merge_list("maps", current, new);
+        assert_eq!(merged, vec![json!({"alias": "sand", "map": "sand"}), json!({"alias": "DUST", "map": "UGC1"})]);
+    }
+
+    #[test]
+    fn merge_keeps_lists_without_identity_unique() {
+        let merged = merge_list("admins", vec![json!(1), json!(2)], vec![json!(2), json!(3)]);
+        assert_eq!(merged, vec![json!(1), json!(2), json!(3)]);
+    }
+
+    #[test]
+    fn identity_is_case_insensitive() {
+        assert_eq!(identity("aliases", &json!(["Dust", "SVR_Dust"])), json!("dust"));
+        assert_eq!(identity("pools", &json!({"name": "Scrim"})), json!("scrim"));
+        assert_eq!(identity("mode_themes", &json!({"gamemode": "SND", "theme": "ww2"})), json!("snd"));
+        assert_eq!(identity("links", &json!({"discord": 5, "steam": 6})), json!(5));
+    }
+
+    #[test]
+    fn sections_are_parsed_by_name() {
+        assert_eq!(parse_sections(&[]).unwrap().len(), SECTIONS.len());
+        assert_eq!(parse_sections(&["Maps", "pools"]).unwrap(), vec!["maps", "pools"]);
+        assert!(parse_sections(&["unknown"]).is_err());
+    }
+
+    #[test]
+    fn describe_lists_added_and_removed_entries() {
+        assert_eq!(describe("admins", &json!([1, 2]), &json!([2, 3])), "\n+ admins: 3\n- admins: 1");
+        assert_eq!(describe("skin_shuffle", &json!(false), &json!(true)), "\n~ skin_shuffle: false -> true");
+        assert_eq!(describe("skin_shuffle", &json!(true), &json!(true)), "");
+    }
+
+    #[test]
+    fn imports_the_commands_would_refuse_are_invalid() {
+        let config = IvanConfig::default();
+        let file = |value: Value| value.to_string().into_bytes();
+        let duplicate = file(json!({"version": CONFIG_VERSION, "aliases": [["dust", "SVR_Dust"], ["DUST", "UGC1"]]}));
+        assert!(dry_run(&config, &duplicate, &["aliases"], ImportMode::Replace).is_err());
+        let dangling = file(json!({"version": CONFIG_VERSION, "pools": [{"name": "scrim", "maps": ["dust"]}]}));
+        assert!(dry_run(&config, &dangling, &["pools"], ImportMode::Replace).is_err());
+        let valid = file(json!({"version": CONFIG_VERSION, "aliases": [["dust", "SVR_Dust"]]}));
+        assert!(dry_run(&config, &valid, &["aliases"], ImportMode::Replace).is_ok());
+    }
+}
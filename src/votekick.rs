@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::channel::Message;
+use crate::audit::audit;
+use crate::background::announce;
+use crate::config::Player;
+use crate::discord::{CustomFramework, ConcurrentFramework};
+use crate::model::{IvanError, BotErrorKind, invalid_argument, get_player_list, get_users_from_channel};
+use crate::output::output;
+use crate::pavlov::{PavlovCommands, pa, parse_number};
+use crate::permissions::PermissionLevel;
+use crate::voting::handle_kick_vote_start;
+use crate::help::{HELP_VOTEKICK, HELP_VOTEKICK_IMMUNE, HELP_VOTEKICK_SETTING, HELP_STEAM_ID};
+
+/// A kick vote passes with at least this many yes votes, however few players there are.
+const MIN_QUORUM: u64 = 2;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VoteKickSettings {
+    /// Minutes before someone can start another kick vote.
+    pub cooldown: u64,
+    /// Minutes a player stays banned after a successful kick vote.
+    pub ban_duration: u64,
+    /// Steam ids that can't be vote kicked.
+    pub immune: Vec<u64>,
+}
+
+impl Default for VoteKickSettings {
+    fn default() -> Self {
+        VoteKickSettings { cooldown: 10, ban_duration: 60, immune: vec![] }
+    }
+}
+
+/// A ban that is lifted by the background thread once `until` (unix seconds) has passed.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TempBan {
+    pub steam_id: u64,
+    pub name: String,
+    pub reason: String,
+    pub until: u64,
+}
+
+/// The player a kick vote is about.
+pub struct KickVote {
+    pub steam_id: u64,
+    pub name: String,
+    pub reason: String,
+    pub initiator: u64,
+    pub quorum: u64,
+}
+
+/// `-votekick player reason`, `-votekick immune [add, remove, list]`, `-votekick [cooldown, ban] minutes`
+/// and `-votekick bans`. Everything except starting a vote needs a mod.
+pub fn handle_votekick(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &mut Context, concurrent_framework: ConcurrentFramework, permission: PermissionLevel) -> Result<(), IvanError> {
+    let first = pa(arguments, 1, HELP_VOTEKICK)?;
+    let moderation = ["immune", "cooldown", "ban", "bans"].contains(&first.to_lowercase().as_str());
+    if moderation && permission > PermissionLevel::Mod {
+        output(ctx, msg, format!("You're not allowed to execute the command: votekick {}, your rank is currently {}", first, permission));
+        return Ok(());
+    }
+    let response = match first.to_lowercase().as_str() {
+        "immune" => handle_immune(arguments, framework)?,
+        "cooldown" | "ban" => {
+            let minutes: u64 = parse_number(pa(arguments, 2, HELP_VOTEKICK_SETTING)?)?;
+            let mut settings = framework.config.get_votekick().clone();
+            if first.eq_ignore_ascii_case("cooldown") { settings.cooldown = minutes } else { settings.ban_duration = minutes }
+            framework.config.set_votekick(settings)?;
+            format!("Set the votekick {} to {} minutes", first.to_lowercase(), minutes)
+        }
+        "bans" => {
+            let now = now();
+            framework.config.get_temp_bans().iter().fold("Temporary bans:".to_string(), |a, ban| {
+                format!("{}\n{} ({}) for {} more minutes: {}", a, ban.name, ban.steam_id, ban.until.saturating_sub(now) / 60 + 1, ban.reason)
+            })
+        }
+        _ => return start_votekick(arguments, framework, msg, ctx, concurrent_framework)
+    };
+    output(ctx, msg, response);
+    Ok(())
+}
+
+fn handle_immune(arguments: &Vec<&str>, framework: &mut CustomFramework) -> Result<String, IvanError> {
+    let mut settings = framework.config.get_votekick().clone();
+    match pa(arguments, 2, HELP_VOTEKICK_IMMUNE)?.to_lowercase().as_str() {
+        "add" => {
+            let steam_id: u64 = parse_number(pa(arguments, 3, HELP_STEAM_ID)?)?;
+            settings.immune.retain(|value| *value != steam_id);
+            settings.immune.push(steam_id);
+            framework.config.set_votekick(settings)?;
+            Ok(format!("{} can't be vote kicked anymore", steam_id))
+        }
+        "remove" => {
+            let steam_id: u64 = parse_number(pa(arguments, 3, HELP_STEAM_ID)?)?;
+            settings.immune.retain(|value| *value != steam_id);
+            framework.config.set_votekick(settings)?;
+            Ok(format!("{} can be vote kicked again", steam_id))
+        }
+        "list" => {
+            let staff = "Admins and mods are immune too once they linked their steam account with -link";
+            Ok(settings.immune.iter().fold("Immune to votekick:".to_string(), |a, id| format!("{}\n{}", a, id)) + "\n" + staff)
+        }
+        x => invalid_argument(x, HELP_VOTEKICK_IMMUNE)
+    }
+}
+
+fn start_votekick(arguments: &Vec<&str>, framework: &mut CustomFramework, msg: &mut Message, ctx: &mut Context, concurrent_framework: ConcurrentFramework) -> Result<(), IvanError> {
+    let initiator = msg.author.id.0;
+    let cooldown = Duration::from_secs(framework.config.get_votekick().cooldown * 60);
+    if let Some(started) = framework.kick_cooldowns.get(&initiator) {
+        if started.elapsed() < cooldown {
+            let minutes = (cooldown - started.elapsed()).as_secs() / 60 + 1;
+            return Err(IvanError { input: format!("you can start another kick vote in {} minutes", minutes), kind: BotErrorKind::InvalidArgument });
+        }
+    }
+    let players = get_player_list(framework)?;
+    let player = find_player(&players, pa(arguments, 1, HELP_VOTEKICK)?)?;
+    let steam_id: u64 = parse_number(player.UniqueId.as_str())?;
+    if framework.config.is_kick_immune(steam_id) {
+        return Err(IvanError { input: format!("{} is an admin, a mod or on the immune list and can't be vote kicked", player.Username), kind: BotErrorKind::InvalidArgument });
+    }
+    let reason = arguments.iter().skip(2).cloned().collect::<Vec<&str>>().join(" ");
+    if reason.is_empty() {
+        return Err(IvanError { input: "give a reason for the kick vote".to_string(), kind: BotErrorKind::MissingArgument });
+    }
+    let users = get_users_from_channel(msg, ctx).unwrap_or_else(|value| {
+        println!("Couldn't retrieve users from channel {}", value);
+        vec![]
+    });
+    let voters = std::cmp::max(players.len(), users.len()) as u64;
+    let quorum = std::cmp::max(MIN_QUORUM, voters / 2 + 1);
+    let kick = KickVote { steam_id, name: player.Username.clone(), reason, initiator, quorum };
+    handle_kick_vote_start(framework, msg, concurrent_framework, users, kick)?;
+    framework.kick_cooldowns.insert(initiator, Instant::now());
+    audit(initiator, format!("started a kick vote against {} ({})", player.Username, steam_id));
+    Ok(())
+}
+
+/// Finds an online player by steam id or by (part of) the name.
+fn find_player<'a>(players: &'a [Player], value: &str) -> Result<&'a Player, IvanError> {
+    if let Some(player) = players.iter().find(|player| player.UniqueId == value) {
+        return Ok(player);
+    }
+    let matching: Vec<&Player> = players.iter().filter(|player| player.Username.to_lowercase().contains(&value.to_lowercase())).collect();
+    match matching.as_slice() {
+        [player] => Ok(player),
+        [] => Err(IvanError { input: format!("there is no player \"{}\" on the server", value), kind: BotErrorKind::InvalidArgument }),
+        _ => Err(IvanError { input: format!("\"{}\" matches several players, use the steam id", value), kind: BotErrorKind::InvalidArgument })
+    }
+}
+
+/// Applies the result of a finished kick vote and describes it.
+pub fn conclude_kick_vote(framework: &mut CustomFramework, kick: &KickVote, yes: u64, no: u64) -> String {
+    if yes < kick.quorum || yes <= no {
+        audit(kick.initiator, format!("kick vote against {} ({}) failed with {} yes and {} no votes", kick.name, kick.steam_id, yes, no));
+        return format!("{} stays, the vote needed {} yes votes and more yes than no votes ({} yes, {} no)", kick.name, kick.quorum, yes, no);
+    }
+    let minutes = framework.config.get_votekick().ban_duration;
+    let response = framework.connection.execute_command(PavlovCommands::Ban(kick.steam_id));
+    let ban = TempBan { steam_id: kick.steam_id, name: kick.name.clone(), reason: kick.reason.clone(), until: now() + minutes * 60 };
+    framework.config.add_temp_ban(ban).unwrap_or_else(|err| {
+        println!("could not record the temporary ban of {}, it has to be lifted by hand: {}", kick.steam_id, err);
+    });
+    audit(kick.initiator, format!("kick vote against {} ({}) passed with {} yes and {} no votes, banned for {} minutes: {}", kick.name, kick.steam_id, yes, no, minutes, kick.reason));
+    format!("{} was banned for {} minutes ({} yes, {} no)\n{}", kick.name, minutes, yes, no, response)
+}
+
+/// Lifts the temporary bans that have expired.
+pub fn expire_temp_bans(framework: &mut CustomFramework, http: &Http) {
+    let now = now();
+    let expired: Vec<TempBan> = framework.config.get_temp_bans().iter().filter(|ban| ban.until <= now).cloned().collect();
+    for ban in expired {
+        framework.connection.execute_command(PavlovCommands::Unban(ban.steam_id));
+        if let Err(err) = framework.config.remove_temp_ban(ban.steam_id) {
+            println!("could not remove the temporary ban of {}: {}", ban.steam_id, err);
+        }
+        audit(0, format!("temporary ban of {} ({}) expired", ban.name, ban.steam_id));
+        announce(http, &framework.config, format!("The temporary ban of {} has expired", ban.name));
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|value| value.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players() -> Vec<Player> {
+        vec![("Ivan", "76561190000000001"), ("IvanTheTerrible", "76561190000000002"), ("Boris", "76561190000000003")].into_iter().map(|(name, id)| {
+            Player { Username: name.to_string(), UniqueId: id.to_string() }
+        }).collect()
+    }
+
+    #[test]
+    fn players_are_found_by_steam_id() {
+        let players = players();
+        assert_eq!(find_player(&players, "76561190000000002").unwrap().Username, "IvanTheTerrible");
+    }
+
+    #[test]
+    fn players_are_found_by_part_of_the_name() {
+        let players = players();
+        assert_eq!(find_player(&players, "bor").unwrap().Username, "Boris");
+        assert_eq!(find_player(&players, "terrible").unwrap().Username, "IvanTheTerrible");
+    }
+
+    #[test]
+    fn ambiguous_or_unknown_names_are_refused() {
+        let players = players();
+        assert!(find_player(&players, "ivan").is_err());
+        assert!(find_player(&players, "natasha").is_err());
+    }
+}
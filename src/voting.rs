@@ -1,20 +1,23 @@
 use std::fmt::{Display, Formatter};
 use core::fmt;
-use serenity::model::channel::{Message, ReactionType, MessageReaction, GuildChannel};
+use serenity::model::channel::{Message, MessageReaction, GuildChannel};
 use serenity::client::Context;
 use crate::discord::{CustomFramework, ConcurrentFramework};
 use std::ops::{Add };
-use serenity::model::id::{MessageId, ChannelId };
+use serenity::model::id::{MessageId, ChannelId, GuildId, UserId };
 use serenity::model::channel::ReactionType::Unicode;
-use crate::model::{IvanError, BotErrorKind, reply, assign_skins };
-use crate::pavlov::{PavlovCommands, GameMode, Skin};
+use crate::model::{IvanError, BotErrorKind, reply, assign_skins, get_player_list, get_server_info };
+use crate::pavlov::{PavlovCommands, GameMode, Skin, TeamId};
 use rand::seq::{IteratorRandom, SliceRandom};
 use serenity::http::{CacheHttp };
 use std::sync::{Arc, Mutex };
 use std::thread::sleep;
 use std::time::{Duration, Instant};
-use crate::config::{GunMode};
+use crate::config::{GunMode, ServerInfo};
+use crate::votekick::{KickVote, conclude_kick_vote};
+use crate::skins::assign_theme;
 use serenity::prelude::RwLock;
+use serenity::builder::CreateEmbed;
 
 const KNIFE: char = '🍴';
 const SALT: char = '🧂';
@@ -27,17 +30,40 @@ const BARF: char = '🤮';
 const TURD: char = '💩';
 const GOBLIN: char = '👺';
 
+const RED_TEAM: TeamId = 1;
+const BLUE_TEAM: TeamId = 0;
+/// A vote for the map that is already running doesn't change the map label, the reload is then
+/// assumed to be done when the round state changed or after this delay.
+const TEAM_SYNC_DELAY: Duration = Duration::from_secs(20);
+const TEAM_SYNC_RETRY: Duration = Duration::from_secs(5);
+const TEAM_SYNC_TIMEOUT: Duration = Duration::from_secs(180);
+
+const YES: char = '👍';
+const NO: char = '👎';
+
 const ALL_VOTE_OPTIONS: [char; 10] = [HUNDRED, KNIFE, SALT, MONEY, CHAMPAGNE, SMIRK, HAND, BARF, TURD, GOBLIN];
 
 
 
+/// A vote is either on a map directly or, for `-map vote modes`, first on the game mode after
+/// which a map vote with the winning mode starts. Kick votes reuse the same reactions with a yes
+/// and a no choice.
+#[derive(Clone, Copy, PartialEq)]
+enum VoteStage {
+    Mode,
+    Map,
+    Kick,
+}
+
 pub struct Vote {
+    stage: VoteStage,
     maps: Vec<Choice>,
     message_id: MessageId,
     channel_id: ChannelId,
     countdown: u64,
     users: Vec<u64>,
     pub teams: Option<(Vec<u64>, Vec<u64>)>,
+    kick: Option<KickVote>,
 }
 
 struct Choice {
@@ -45,32 +71,98 @@ struct Choice {
     map: String,
     alias: String,
     gamemode: GameMode,
+    title: Option<String>,
+    preview_url: Option<String>,
 }
 
-impl Display for Vote {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let timer_message = format!("\nThe vote will end in: \"{}\" seconds", self.countdown);
+const BAR_LENGTH: u64 = 10;
 
-        let message: String = self.maps.iter().map(|element| {
-            format!("Vote: {} for map: \"{}\" gamemode: {}", element.id, element.alias, element.gamemode)
-        }).fold(timer_message, |a, b| {
-            let with_enter = a.add("\n");
-            with_enter.add(b.as_str())
-        });
-        write!(f, "{}", message)?;
+impl Vote {
+    fn new(stage: VoteStage, maps: Vec<Choice>, channel_id: ChannelId, users: Vec<u64>, teams: Option<(Vec<u64>, Vec<u64>)>) -> Vote {
+        Vote { stage, maps, message_id: MessageId(0), channel_id, countdown: 30, users, teams, kick: None }
+    }
 
+    fn mentions(&self) -> String {
         match &self.teams {
-            Some((team1, team2)) => {
-                write!(f, "\nRed team: {}", format_users(team1))?;
-                write!(f, "\nBlue team: {}", format_users(team2))
-            }
-            None => {
-                write!(f, "\nGet ready to vote: {}", format_users(&self.users))
+            Some((team1, team2)) => format!("Get ready to vote: {} {}", format_users(team1), format_users(team2)),
+            None => format!("Get ready to vote: {}", format_users(&self.users))
+        }
+    }
+
+    /// Counts the reactions per choice, in the same order as `maps`. The reaction the bot adds
+    /// itself is not counted.
+    fn tally(&self, message: &Message) -> Vec<u64> {
+        self.maps.iter().map(|choice| {
+            message.reactions.iter().find(|reaction| {
+                match &reaction.reaction_type {
+                    Unicode(value) => *value == choice.id,
+                    _ => false
+                }
+            }).map(|reaction| if reaction.me { reaction.count.saturating_sub(1) } else { reaction.count }).unwrap_or(0)
+        }).collect()
+    }
+
+    fn embed<'a>(&self, tally: &[u64], embed: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        match self.stage {
+            VoteStage::Mode => embed.title("Game mode vote").description("React with the emoji of the game mode you want to play, a map vote for it follows"),
+            VoteStage::Map => embed.title("Map vote").description("React with the emoji of the map you want to play"),
+            VoteStage::Kick => match &self.kick {
+                Some(kick) => embed.title(format!("Vote kick: {}", kick.name)).description(format!("Reason: {}\n{} yes votes and more yes than no votes are needed to ban", kick.reason, kick.quorum)),
+                None => embed.title("Vote kick")
             }
+        };
+        self.choice_fields(tally, embed);
+        if let Some(preview_url) = self.leader(tally).and_then(|choice| choice.preview_url.clone()) {
+            embed.thumbnail(preview_url);
         }
+        if let Some((team1, team2)) = &self.teams {
+            embed.field("Red team", or_nobody(format_users(team1)), true);
+            embed.field("Blue team", or_nobody(format_users(team2)), true);
+        }
+        embed.footer(|footer| footer.text(format!("The vote will end in: {} seconds", self.countdown)))
+    }
+
+    fn results_embed<'a>(&self, tally: &[u64], winner: &Choice, embed: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        match self.stage {
+            VoteStage::Mode => embed.title("Game mode vote results").description(format!("The winner is: {}, the map vote starts now", winner.gamemode)),
+            VoteStage::Map => embed.title("Map vote results").description(format!("The winner is: {}", winner)),
+            VoteStage::Kick => embed.title("Vote kick results")
+        };
+        self.choice_fields(tally, embed);
+        if let Some(preview_url) = &winner.preview_url {
+            embed.image(preview_url);
+        }
+        if let Some((team1, team2)) = &self.teams {
+            embed.field("Red team", or_nobody(format_users(team1)), true);
+            embed.field("Blue team", or_nobody(format_users(team2)), true);
+        }
+        embed.footer(|footer| footer.text("The vote has ended"))
+    }
+
+    fn choice_fields(&self, tally: &[u64], embed: &mut CreateEmbed) {
+        let total: u64 = tally.iter().sum();
+        for (choice, count) in self.maps.iter().zip(tally) {
+            let percentage = (count * 100).checked_div(total).unwrap_or(0);
+            let filled = percentage * BAR_LENGTH / 100;
+            let bar = format!("{}{}", "█".repeat(filled as usize), "░".repeat((BAR_LENGTH - filled) as usize));
+            let (name, detail) = match self.stage {
+                VoteStage::Mode => (choice.gamemode.to_string(), choice.title.clone().unwrap_or_default()),
+                VoteStage::Map => (choice.name(), choice.gamemode.to_string()),
+                VoteStage::Kick => (choice.alias.clone(), "".to_string())
+            };
+            embed.field(format!("{} {}", choice.id, name), format!("{}\n{} {} votes ({}%)", detail, bar, count, percentage), false);
+        }
+    }
+
+    fn leader(&self, tally: &[u64]) -> Option<&Choice> {
+        self.maps.iter().zip(tally).filter(|(_, count)| **count > 0).max_by_key(|(_, count)| **count).map(|(choice, _)| choice)
     }
 }
 
+fn or_nobody(users: String) -> String {
+    if users.is_empty() { "nobody".to_string() } else { users }
+}
+
 fn format_users(vec: &Vec<u64>) -> String {
     let value = vec.iter().fold("".to_string(), |a, b| {
         a.add(format!("<@{}> ", b).as_str())
@@ -78,59 +170,133 @@ fn format_users(vec: &Vec<u64>) -> String {
     value.trim_end().to_string()
 }
 
+impl Choice {
+    fn name(&self) -> String {
+        match &self.title {
+            Some(title) => format!("\"{}\" ({})", title, self.alias),
+            None => format!("\"{}\"", self.alias)
+        }
+    }
+}
+
 impl Display for Choice {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{} gamemode: {}", self.alias, self.gamemode)
+        write!(f, "{} gamemode: {}", self.name(), self.gamemode)
     }
 }
 
 pub fn handle_vote_start(framework: &mut CustomFramework, msg: &mut Message, ctx: &mut Context, concurrent_framework: ConcurrentFramework, game_mode: Option<GameMode>, users: Vec<u64>, teams: Option<(Vec<u64>, Vec<u64>)>) -> Result<(), IvanError> {
-    match &mut framework.vote {
-        Some(_) => {
-            Err(IvanError {
-                input: "".to_string(),
-                kind: BotErrorKind::VoteInProgress,
-            })
+    check_no_vote(framework)?;
+    if framework.config.get_maps().is_empty() {
+        reply(msg, ctx.http(), "Can not start a vote without a map pool, add maps with -map add [url/map] [gamemode] [alias]".to_string())?;
+        return Ok(());
+    }
+    let vote = Vote::new(VoteStage::Map, map_choices(framework, game_mode)?, msg.channel_id, users, teams);
+    start_vote(framework, concurrent_framework, vote)
+}
+
+/// Starts a vote on the game modes of the pool, the winning mode gets a map vote of its own.
+pub fn handle_mode_vote_start(framework: &mut CustomFramework, msg: &mut Message, concurrent_framework: ConcurrentFramework, users: Vec<u64>) -> Result<(), IvanError> {
+    check_no_vote(framework)?;
+    let vote = Vote::new(VoteStage::Mode, mode_choices(framework)?, msg.channel_id, users, None);
+    start_vote(framework, concurrent_framework, vote)
+}
+
+/// Starts a yes/no vote on banning a player, see `votekick.rs`.
+pub fn handle_kick_vote_start(framework: &mut CustomFramework, msg: &mut Message, concurrent_framework: ConcurrentFramework, users: Vec<u64>, kick: KickVote) -> Result<(), IvanError> {
+    check_no_vote(framework)?;
+    let choices = [(YES, "Yes"), (NO, "No")].iter().map(|(emoji, answer)| {
+        Choice { id: emoji.to_string(), map: "".to_string(), alias: answer.to_string(), gamemode: GameMode::named(""), title: None, preview_url: None }
+    }).collect();
+    let vote = Vote { kick: Some(kick), ..Vote::new(VoteStage::Kick, choices, msg.channel_id, users, None) };
+    start_vote(framework, concurrent_framework, vote)
+}
+
+/// Map, game mode and kick votes share one vote message, the error says which vote has to end first.
+fn check_no_vote(framework: &CustomFramework) -> Result<(), IvanError> {
+    match &framework.vote {
+        Some(vote) => {
+            let running = match (&vote.stage, &vote.kick) {
+                (VoteStage::Kick, Some(kick)) => format!("a kick vote against {}", kick.name),
+                (VoteStage::Mode, _) => "a game mode vote".to_string(),
+                _ => "a map vote".to_string()
+            };
+            Err(IvanError { input: format!("{} ends in {} seconds, try again after it", running, vote.countdown), kind: BotErrorKind::VoteInProgress })
         }
-        None => {
-            let maps = framework.config.get_maps_random(game_mode)?;
-            if maps.is_empty() {
-                reply(msg, ctx.http(), "Can not start a vote without a map pool, add maps with -map add [url/map] [gamemode] [alias]".to_string())?;
-                return Ok(());
-            }
-            let emojis = get_random_emojis(framework.config.get_vote_amount() as usize)?;
-            let choices: Vec<Choice> = maps.iter().zip(emojis).map(|(poolmap, emoji)| {
-                Choice {
-                    id: emoji.to_string(),
-                    map: poolmap.map.clone(),
-                    alias: poolmap.alias.clone(),
-                    gamemode: handle_gunmode(poolmap.gamemode.clone(), framework.config.get_gun_mode()),
-                }
-            }).collect();
-
-            let mut vote = Vote { maps: choices, message_id: MessageId(0), channel_id: msg.channel_id, countdown: 30, users, teams };
-            let mut reply = reply(msg, ctx.http(), vote.to_string())?;
-            for x in &vote.maps {
-                _react(&mut reply, ctx, &Unicode(x.id.clone())).map_err(|_| {
-                    IvanError { kind: BotErrorKind::CouldNotReply, input: "tried to react".to_string() }
-                })?;
-            }
-            vote.message_id = reply.id;
-            framework.vote = Some(vote);
+        None => Ok(())
+    }
+}
 
-            vote_thread(concurrent_framework.data.clone(), concurrent_framework);
-            Ok(())
+fn start_vote(framework: &mut CustomFramework, concurrent_framework: ConcurrentFramework, mut vote: Vote) -> Result<(), IvanError> {
+    let channel_id = vote.channel_id;
+    let http = concurrent_framework.http.clone();
+    let reply = channel_id.send_message(&http, |m| {
+        m.content(vote.mentions()).embed(|e| vote.embed(&vec![0; vote.maps.len()], e))
+    }).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::CouldNotReply }
+    })?;
+    for x in &vote.maps {
+        http.create_reaction(channel_id.0, reply.id.0, &Unicode(x.id.clone())).map_err(|_| {
+            IvanError { kind: BotErrorKind::CouldNotReply, input: "tried to react".to_string() }
+        })?;
+    }
+    vote.message_id = reply.id;
+    framework.vote = Some(vote);
+
+    vote_thread(concurrent_framework.data.clone(), concurrent_framework);
+    Ok(())
+}
+
+fn map_choices(framework: &CustomFramework, game_mode: Option<GameMode>) -> Result<Vec<Choice>, IvanError> {
+    let maps = framework.config.get_maps_random(game_mode)?;
+    let emojis = get_random_emojis(framework.config.get_vote_amount() as usize)?;
+    Ok(maps.iter().zip(emojis).map(|(poolmap, emoji)| {
+        let metadata = framework.config.find_workshop_metadata(&poolmap.map);
+        Choice {
+            id: emoji.to_string(),
+            map: poolmap.map.clone(),
+            alias: poolmap.alias.clone(),
+            gamemode: handle_gunmode(poolmap.gamemode.clone(), framework.config.get_gun_mode()),
+            title: metadata.map(|value| value.title.clone()),
+            preview_url: metadata.and_then(|value| value.preview_url.clone()),
         }
+    }).collect())
+}
+
+/// One choice per game mode with enough pool maps, the mode description is shown as title.
+fn mode_choices(framework: &CustomFramework) -> Result<Vec<Choice>, IvanError> {
+    let amount = framework.config.get_vote_amount() as usize;
+    let mut modes = framework.config.get_vote_modes();
+    if modes.len() < 2 {
+        return Err(IvanError {
+            input: "Could not start a game mode vote because the map pool didn't have at least 2 maps for at least 2 game modes".to_string(),
+            kind: BotErrorKind::InvalidVoteAmount,
+        });
+    }
+    if modes.len() > amount {
+        modes = modes.into_iter().choose_multiple(&mut rand::thread_rng(), amount);
     }
+    let emojis = get_random_emojis(modes.len())?;
+    let catalog = framework.config.get_catalog();
+    Ok(modes.into_iter().zip(emojis).map(|(mode, emoji)| {
+        Choice {
+            id: emoji.to_string(),
+            map: "".to_string(),
+            alias: mode.to_string(),
+            title: catalog.find_mode(mode.name()).map(|entry| entry.description.clone()).filter(|description| !description.is_empty()),
+            preview_url: None,
+            gamemode: mode,
+        }
+    }).collect())
 }
 
 fn handle_gunmode(gamemode: GameMode, gun_mode: GunMode) -> GameMode {
-    if gamemode == GameMode::GUN && gun_mode == GunMode::WW2 {
-        GameMode::WW2GUN
-    } else if gamemode == GameMode::GUN && gun_mode == GunMode::OitcRandom {
-        vec![GameMode::GUN, GameMode::WW2GUN, GameMode::OITC].choose(&mut rand::thread_rng()).unwrap().clone()
-    } else if gamemode == GameMode::GUN && gun_mode == GunMode::Random {
-        vec![GameMode::GUN, GameMode::WW2GUN].choose(&mut rand::thread_rng()).unwrap().clone()
+    if gamemode.is(GameMode::GUN) && gun_mode == GunMode::WW2 {
+        GameMode::named(GameMode::WW2GUN)
+    } else if gamemode.is(GameMode::GUN) && gun_mode == GunMode::OitcRandom {
+        GameMode::named([GameMode::GUN, GameMode::WW2GUN, GameMode::OITC].choose(&mut rand::thread_rng()).unwrap())
+    } else if gamemode.is(GameMode::GUN) && gun_mode == GunMode::Random {
+        GameMode::named([GameMode::GUN, GameMode::WW2GUN].choose(&mut rand::thread_rng()).unwrap())
     } else {
         gamemode
     }
@@ -142,14 +308,27 @@ fn vote_thread(framework_arc: Arc<Mutex<CustomFramework>>, cache: ConcurrentFram
             println!("waiting for vote failed because: {}", error);
             return;
         });
-        let (mut msg, skin_shuffle) = match framework_arc.lock() {
+        let (mut msg, skin_shuffle, theme, voted) = match framework_arc.lock() {
             Ok(mut value) => {
                 if let Some(vote) = &value.vote {
                     let mut message = cache.http().get_message(vote.channel_id.clone().0, vote.message_id.clone().0).unwrap();
-                    handle_vote_finish(&mut value, &mut message, &cache).unwrap_or_else(|err| {
-                        println!("{}", err)
-                    });
-                    (message, value.config.get_skin_shuffle())
+                    if vote.stage != VoteStage::Map {
+                        let finish = if vote.stage == VoteStage::Mode { handle_mode_vote_finish } else { handle_kick_vote_finish };
+                        finish(&mut value, &mut message, &cache).unwrap_or_else(|err| {
+                            println!("{}", err);
+                            reply(&mut message, cache.http(), err.to_string()).map(|_| ()).unwrap_or_else(|err| println!("{}", err));
+                        });
+                        return;
+                    }
+                    let voted = match handle_vote_finish(&mut value, &mut message, &cache) {
+                        Ok(voted) => voted,
+                        Err(err) => {
+                            println!("{}", err);
+                            return;
+                        }
+                    };
+                    let theme = value.config.get_mode_theme(&voted.gamemode);
+                    (message, value.config.get_skin_shuffle(), theme, voted)
                 } else {
                     return;
                 }
@@ -159,11 +338,18 @@ fn vote_thread(framework_arc: Arc<Mutex<CustomFramework>>, cache: ConcurrentFram
                 return ();
             }
         };
-        if skin_shuffle {
-            sleep(Duration::from_secs(90));
+        if skin_shuffle || theme.is_some() {
+            if !wait_for_round(&framework_arc, &voted) {
+                reply(&mut msg, cache.http(), format!("The server did not start a round on {}, so no skins were given", voted.map)).map(|_| ()).unwrap_or_else(|err| println!("{}", err));
+                return;
+            }
             match framework_arc.lock() {
                 Ok(mut value) => {
-                    reply(&mut msg, &cache.http(), assign_skins(&mut value, Skin::get_random).unwrap_or_else(|error| { error.to_string() })).unwrap_or_else(|value| {
+                    let skins = match &theme {
+                        Some(theme) => assign_theme(&mut value, theme),
+                        None => assign_skins(&mut value, Skin::get_random)
+                    };
+                    reply(&mut msg, cache.http(), skins.unwrap_or_else(|error| { error.to_string() })).unwrap_or_else(|value| {
                         println!("{}", value);
                         msg
                     });
@@ -210,7 +396,8 @@ fn update_vote(framework_clone: Arc<Mutex<CustomFramework>>, cache_clone: &Concu
                     let mut message = cache_clone.http().get_message(vote.channel_id.clone().0, vote.message_id.clone().0).map_err(|err| {
                         IvanError { input: format!("{}", err), kind: BotErrorKind::MessageRetrieveError }
                     })?;
-                    message.edit(cache_clone.http(), |m| { m.content(format!("{}", vote)) }).map_err(|err| {
+                    let tally = vote.tally(&message);
+                    message.edit(cache_clone.http(), |m| { m.content(vote.mentions()).embed(|e| vote.embed(&tally, e)) }).map_err(|err| {
                         IvanError { input: format!("{}", err), kind: BotErrorKind::MessageEditError }
                     })?;
                     Ok(())
@@ -226,48 +413,192 @@ fn update_vote(framework_clone: Arc<Mutex<CustomFramework>>, cache_clone: &Concu
 }
 
 
-fn _react(msg: &mut Message, cache_http: &mut impl CacheHttp, reaction_type: &ReactionType) -> serenity::Result<()> {
-    cache_http.http().create_reaction(msg.channel_id.clone().0, msg.id.clone().0, reaction_type)
+/// Ends the game mode vote and starts the map vote for the winning mode with the same voters.
+fn handle_mode_vote_finish(framework: &mut CustomFramework, msg: &mut Message, ctx: &ConcurrentFramework) -> Result<(), IvanError> {
+    let vote = framework.vote.take().ok_or(IvanError { input: "".to_string(), kind: BotErrorKind::VoteNotInProgress })?;
+    let winner = determine_winner(&vote, msg);
+    let tally = vote.tally(msg);
+    msg.edit(ctx.http(), |m| { m.content("").embed(|e| vote.results_embed(&tally, winner, e)) }).map_err(|err| {
+        IvanError { input: format!("{}", err), kind: BotErrorKind::MessageEditError }
+    })?;
+    let map_vote = Vote::new(VoteStage::Map, map_choices(framework, Some(winner.gamemode.clone()))?, vote.channel_id, vote.users.clone(), None);
+    start_vote(framework, ctx.clone(), map_vote)
 }
 
+/// Ends a kick vote, the outcome replaces the vote message.
+fn handle_kick_vote_finish(framework: &mut CustomFramework, msg: &mut Message, ctx: &ConcurrentFramework) -> Result<(), IvanError> {
+    let vote = framework.vote.take().ok_or(IvanError { input: "".to_string(), kind: BotErrorKind::VoteNotInProgress })?;
+    let kick = vote.kick.as_ref().ok_or(IvanError { input: "the kick vote has no player".to_string(), kind: BotErrorKind::VoteNotInProgress })?;
+    let tally = vote.tally(msg);
+    let outcome = conclude_kick_vote(framework, kick, tally[0], tally[1]);
+    msg.edit(ctx.http(), |m| {
+        m.content("").embed(|e| {
+            vote.choice_fields(&tally, e);
+            e.title(format!("Vote kick results: {}", kick.name)).description(outcome).footer(|footer| footer.text("The vote has ended"))
+        })
+    }).map_err(|err| {
+        IvanError { input: format!("{}", err), kind: BotErrorKind::MessageEditError }
+    })?;
+    Ok(())
+}
 
-pub fn handle_vote_finish(framework: &mut CustomFramework, msg: &mut Message,ctx: &ConcurrentFramework) -> Result<(), IvanError> {
+
+/// The map a finished vote switched to, `before` is the server info from before the switch.
+pub struct VotedMap {
+    pub map: String,
+    pub gamemode: GameMode,
+    pub before: Option<ServerInfo>,
+}
+
+/// Switches to the winning map and returns it.
+pub fn handle_vote_finish(framework: &mut CustomFramework, msg: &mut Message,ctx: &ConcurrentFramework) -> Result<VotedMap, IvanError> {
     match &mut framework.vote {
         Some(vote) => {
             let mut message = ctx.http().get_message(vote.channel_id.clone().0, vote.message_id.clone().0).unwrap();
             let winner = determine_winner(vote, &mut message);
-            reply(msg, &ctx.http(), format!("The winner is: {}", winner))?;
-            let response = framework.connection.execute_command(PavlovCommands::SwitchMap { map: winner.map.clone(), gamemode: winner.gamemode.clone() });
-            reply(msg, &ctx.http(), response)?;
+            let tally = vote.tally(&message);
+            message.edit(ctx.http(), |m| { m.content("").embed(|e| vote.results_embed(&tally, winner, e)) }).map_err(|err| {
+                IvanError { input: format!("{}", err), kind: BotErrorKind::MessageEditError }
+            })?;
+            let gamemode = winner.gamemode.clone();
+            let map = winner.map.clone();
             let teams = vote.teams.clone();
+            let before = get_server_info(framework).ok();
+            let response = framework.connection.execute_command(PavlovCommands::SwitchMap { map: map.clone(), gamemode: gamemode.clone() });
+            reply(msg, ctx.http(), response)?;
 
             framework.vote = None;
             match teams {
                 None => {println!("there are no teams so no moving")}
                 Some((team1, team2)) => {
-                    return match framework.config.get_team_channels() {
-                        Some((channel1, channel2)) => {
-                            let channel_1 = get_channel(ctx, channel1)?;
-                            let channel_2 = get_channel(ctx, channel2)?;
-                            move_to_channel(ctx, channel_1.clone(), channel_2.clone(), team2)?;
-                            move_to_channel(ctx, channel_2.clone(), channel_1.clone(), team1)?;
-
-                            Ok(())
+                    apply_teams_in_game(ctx.clone(), msg.channel_id, map.clone(), before.clone(), team1.clone(), team2.clone());
+                    // the map is already switched, skins still follow when the channels can't be used
+                    move_vote_teams(framework, msg, ctx, team1, team2).unwrap_or_else(|err| println!("{}", err));
+                }
+            }
+            Ok(VotedMap { map, gamemode, before })
+        }
+        None => Err(IvanError { input: "".to_string(), kind: BotErrorKind::VoteNotInProgress })
+    }
+}
+
+/// Moves the vote teams to the team voice channels.
+fn move_vote_teams(framework: &CustomFramework, msg: &mut Message, ctx: &ConcurrentFramework, team1: Vec<u64>, team2: Vec<u64>) -> Result<(), IvanError> {
+    match framework.config.get_team_channels() {
+        Some((channel1, channel2)) => {
+            let channel_1 = get_channel(ctx, channel1)?;
+            let channel_2 = get_channel(ctx, channel2)?;
+            move_to_channel(ctx, channel_1.clone(), channel_2.clone(), team2)?;
+            move_to_channel(ctx, channel_2.clone(), channel_1.clone(), team1)?;
+            Ok(())
+        }
+        None => {
+            reply(msg, ctx.http(), "No team channels configured so users will not be moved".to_string())?;
+            Ok(())
+        }
+    }
+}
+
+/// Puts the linked players of the vote teams in the matching in-game team once ServerInfo shows
+/// that `map` is loaded, `before` is the server info from before the map switch. Players that are
+/// not on the server yet are retried until `TEAM_SYNC_TIMEOUT`, after which the bot reports who
+/// could not be placed.
+fn apply_teams_in_game(ctx: ConcurrentFramework, channel_id: ChannelId, map: String, before: Option<ServerInfo>, red: Vec<u64>, blue: Vec<u64>) {
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut pending: Vec<(u64, TeamId)> = red.iter().map(|user| (*user, RED_TEAM)).chain(blue.iter().map(|user| (*user, BLUE_TEAM))).collect();
+        let mut unlinked: Vec<u64> = vec![];
+        let mut loaded = false;
+        loop {
+            sleep(TEAM_SYNC_RETRY);
+            match ctx.data.lock() {
+                Ok(mut framework) => {
+                    loaded = loaded || get_server_info(&mut framework).map(|info| map_loaded(&info, &map, &before, start.elapsed())).unwrap_or(false);
+                    if !loaded {
+                        if start.elapsed() > TEAM_SYNC_TIMEOUT {
+                            break;
                         }
-                        None => {
-                            reply(msg, &ctx.http(), "No team channels configured so users will not be moved".to_string())?;
-                            Ok(())
+                        continue;
+                    }
+                    let online: Vec<String> = get_player_list(&mut framework).map(|players| {
+                        players.into_iter().map(|player| player.UniqueId).collect()
+                    }).unwrap_or_default();
+                    let mut remaining = vec![];
+                    for (user, team) in pending {
+                        match framework.config.find_steam_link(user) {
+                            None => unlinked.push(user),
+                            Some(steam) if online.contains(&steam.to_string()) => {
+                                framework.connection.execute_command(PavlovCommands::SwitchTeam(steam, team));
+                            }
+                            Some(_) => remaining.push((user, team))
                         }
-                    };
+                    }
+                    pending = remaining;
                 }
+                Err(err) => {
+                    println!("could not apply the vote teams, mutex error {}", err);
+                    return;
+                }
+            }
+            if pending.is_empty() || start.elapsed() > TEAM_SYNC_TIMEOUT {
+                break;
             }
-            return Ok(());
         }
-        None => Err(IvanError { input: "".to_string(), kind: BotErrorKind::VoteNotInProgress })
+        let mut report = vec![];
+        if !loaded {
+            report.push(format!("The server did not report {} as the running map, so the vote teams were not placed", map));
+        } else if !pending.is_empty() {
+            let users: Vec<u64> = pending.iter().map(|(user, _)| *user).collect();
+            report.push(format!("Not on the server, so not placed in their team: {}", format_users(&users)));
+        }
+        if !unlinked.is_empty() {
+            report.push(format!("Not linked to a steam account (use -link), so not placed in their team: {}", format_users(&unlinked)));
+        }
+        if !report.is_empty() {
+            channel_id.say(ctx.http(), report.join("\n")).map(|_| ()).unwrap_or_else(|err| {
+                println!("could not report the team placement: {}", err);
+            });
+        }
+    });
+}
+
+/// Whether `info` shows that the server runs `map` since the switch. When it was already running
+/// before, the round state has to change or `TEAM_SYNC_DELAY` has to pass.
+fn map_loaded(info: &ServerInfo, map: &str, before: &Option<ServerInfo>, elapsed: Duration) -> bool {
+    if !info.MapLabel.eq_ignore_ascii_case(map) {
+        return false;
+    }
+    match before {
+        Some(before) if before.MapLabel.eq_ignore_ascii_case(map) => before.RoundState != info.RoundState || elapsed >= TEAM_SYNC_DELAY,
+        _ => true
     }
 }
 
-fn move_to_channel(ctx: &ConcurrentFramework, channel_from: Arc<RwLock<GuildChannel>>, channel_to: Arc<RwLock<GuildChannel>>, team: Vec<u64>) -> Result<(), IvanError> {
+/// Waits until the voted map is loaded and its round has started, so skins reach spawned players.
+/// Gives up after `TEAM_SYNC_TIMEOUT`.
+fn wait_for_round(framework_arc: &Arc<Mutex<CustomFramework>>, voted: &VotedMap) -> bool {
+    let start = Instant::now();
+    let mut loaded = false;
+    while start.elapsed() < TEAM_SYNC_TIMEOUT {
+        sleep(TEAM_SYNC_RETRY);
+        let info = match framework_arc.lock() {
+            Ok(mut framework) => get_server_info(&mut framework),
+            Err(err) => {
+                println!("mutex error {}", err);
+                return false;
+            }
+        };
+        if let Ok(info) = info {
+            loaded = loaded || map_loaded(&info, &voted.map, &voted.before, start.elapsed());
+            if loaded && info.RoundState.eq_ignore_ascii_case("Started") {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn move_to_channel(ctx: &ConcurrentFramework, channel_from: Arc<RwLock<GuildChannel>>, channel_to: Arc<RwLock<GuildChannel>>, team: Vec<u64>) -> Result<(), IvanError> {
     for member in channel_from.read() .members(&ctx.cache).map_err(|err| {
         IvanError { input: err.to_string(), kind: BotErrorKind::DiscordError }
     })? {
@@ -282,7 +613,7 @@ fn move_to_channel(ctx: &ConcurrentFramework, channel_from: Arc<RwLock<GuildChan
     Ok(())
 }
 
-fn get_channel(ctx: &ConcurrentFramework, channel: u64) -> Result<Arc<RwLock<GuildChannel>>, IvanError> {
+pub fn get_channel(ctx: &ConcurrentFramework, channel: u64) -> Result<Arc<RwLock<GuildChannel>>, IvanError> {
     let dc_channel = ctx.http().get_channel(channel).map_err(|err| {
         IvanError { input: err.to_string(), kind: BotErrorKind::DiscordError }
     })?;
@@ -292,6 +623,23 @@ fn get_channel(ctx: &ConcurrentFramework, channel: u64) -> Result<Arc<RwLock<Gui
     Ok(guild_channel)
 }
 
+pub fn channel_members(ctx: &ConcurrentFramework, channel: &Arc<RwLock<GuildChannel>>) -> Result<Vec<u64>, IvanError> {
+    let members = channel.read().members(&ctx.cache).map_err(|err| {
+        IvanError { input: err.to_string(), kind: BotErrorKind::DiscordError }
+    })?;
+    Ok(members.iter().map(|member| member.user_id().0).collect())
+}
+
+/// The voice channel `user` is connected to, from the voice states in the cache.
+pub fn get_voice_channel(ctx: &ConcurrentFramework, guild_id: GuildId, user: u64) -> Result<Option<Arc<RwLock<GuildChannel>>>, IvanError> {
+    let channel_id = ctx.cache.read().guild(guild_id).and_then(|guild| {
+        guild.read().voice_states.get(&UserId(user)).and_then(|state| state.channel_id)
+    });
+    match channel_id {
+        Some(channel_id) => Ok(Some(get_channel(ctx, channel_id.0)?)),
+        None => Ok(None)
+    }
+}
 
 fn determine_winner<'a>(vote: &'a Vote, msg: &mut Message) -> &'a Choice {
     let emoji: Vec<(&MessageReaction, &String)> = msg.reactions.iter().filter_map(|reaction| {
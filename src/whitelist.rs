@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use crate::audit::audit;
+use crate::background::announce;
+use crate::config::{IvanConfig, Player};
+use crate::discord::CustomFramework;
+use crate::model::{IvanError, invalid_argument};
+use crate::pavlov::{PavlovCommands, pa, parse_number};
+use crate::help::{HELP_WHITELIST, HELP_STEAM_ID, HELP_WHITELIST_GRACE};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Whitelist {
+    pub enabled: bool,
+    /// Seconds a player that is not on the whitelist may stay before being kicked.
+    pub grace: u64,
+    pub players: Vec<u64>,
+}
+
+impl Default for Whitelist {
+    fn default() -> Self {
+        Whitelist { enabled: false, grace: 60, players: vec![] }
+    }
+}
+
+pub fn handle_whitelist(arguments: &Vec<&str>, config: &mut IvanConfig, author: u64) -> Result<String, IvanError> {
+    let mut whitelist = config.get_whitelist().clone();
+    let response = match pa(arguments, 1, HELP_WHITELIST)?.to_lowercase().as_str() {
+        "on" => {
+            whitelist.enabled = true;
+            let unlinked = config.unlinked_staff();
+            let staff = if unlinked.is_empty() {
+                "".to_string()
+            } else {
+                let mentions: Vec<String> = unlinked.iter().map(|discord| format!("<@{}>", discord)).collect();
+                format!("\nAdmins and mods are only exempt once they linked their steam account with -link, not linked yet: {}", mentions.join(" "))
+            };
+            format!("Whitelist enabled, players that are not listed are kicked after {} seconds{}", whitelist.grace, staff)
+        }
+        "off" => {
+            whitelist.enabled = false;
+            "Whitelist disabled".to_string()
+        }
+        "add" => {
+            let steam_id: u64 = parse_number(pa(arguments, 2, HELP_STEAM_ID)?)?;
+            whitelist.players.retain(|value| *value != steam_id);
+            whitelist.players.push(steam_id);
+            format!("Added {} to the whitelist", steam_id)
+        }
+        "remove" => {
+            let steam_id: u64 = parse_number(pa(arguments, 2, HELP_STEAM_ID)?)?;
+            whitelist.players.retain(|value| *value != steam_id);
+            format!("Removed {} from the whitelist", steam_id)
+        }
+        "grace" => {
+            whitelist.grace = parse_number(pa(arguments, 2, HELP_WHITELIST_GRACE)?)?;
+            format!("Players that are not listed are kicked after {} seconds", whitelist.grace)
+        }
+        "list" => {
+            let state = if whitelist.enabled { "on" } else { "off" };
+            return Ok(whitelist.players.iter().fold(format!("Whitelist ({}, {} seconds grace):", state, whitelist.grace), |a, id| format!("{}\n{}", a, id)));
+        }
+        x => return invalid_argument(x, HELP_WHITELIST)
+    };
+    config.set_whitelist(whitelist)?;
+    audit(author, response.clone());
+    Ok(response)
+}
+
+/// Kicks players that are not whitelisted once their grace period is over. `seen` remembers
+/// when each of those players was first noticed on the server, kicked players are kept so a
+/// rejoin doesn't start a new grace period.
+pub fn enforce_whitelist(framework: &mut CustomFramework, http: &Http, players: &[Player], seen: &mut HashMap<u64, Instant>) {
+    let whitelist = framework.config.get_whitelist().clone();
+    let grace = Duration::from_secs(whitelist.grace);
+    let mut outsiders: Vec<u64> = vec![];
+    for player in players {
+        let steam_id: u64 = match player.UniqueId.parse() {
+            Ok(value) => value,
+            Err(_) => continue
+        };
+        if whitelist.players.contains(&steam_id) || framework.config.is_staff_player(steam_id) {
+            continue;
+        }
+        outsiders.push(steam_id);
+        let first_seen = *seen.entry(steam_id).or_insert_with(Instant::now);
+        if first_seen.elapsed() >= grace {
+            framework.connection.execute_command(PavlovCommands::Kick(steam_id));
+            audit(0, format!("whitelist kicked {} ({})", player.Username, steam_id));
+            announce(http, &framework.config, format!("Kicked {} ({}) because they are not on the whitelist", player.Username, steam_id));
+        }
+    }
+    seen.retain(|steam_id, first_seen| outsiders.contains(steam_id) || first_seen.elapsed() >= grace);
+}
@@ -0,0 +1,194 @@
+use std::env::var;
+use std::fs;
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serenity::model::channel::Message;
+use crate::config::IvanConfig;
+use crate::model::{IvanError, BotErrorKind, invalid_argument};
+use crate::pavlov::pa;
+use crate::help::HELP_WORKSHOP_ARGUMENT;
+
+/// Steam workshop details for a map, keyed by the `UGC` id the server uses.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WorkshopMetadata {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub preview_url: Option<String>,
+    #[serde(default)]
+    pub file_size: u64,
+    #[serde(default)]
+    pub modes: Vec<String>,
+}
+
+/// Source of workshop metadata for `-workshop fetch`.
+pub trait WorkshopFetcher {
+    fn fetch(&self, ids: &[String]) -> Result<Vec<WorkshopMetadata>, IvanError>;
+}
+
+/// Reads a `GetPublishedFileDetails` dump from disk (WORKSHOP_DUMP).
+pub struct DumpFetcher {
+    path: String,
+}
+
+/// Runs an external command (WORKSHOP_FETCH_COMMAND) with the workshop ids as arguments, the
+/// command has to print a dump to stdout. This keeps the bot itself free of any Steam API code.
+pub struct CommandFetcher {
+    command: String,
+}
+
+impl WorkshopFetcher for DumpFetcher {
+    fn fetch(&self, ids: &[String]) -> Result<Vec<WorkshopMetadata>, IvanError> {
+        let file = fs::read(&self.path).map_err(|err| {
+            IvanError { input: format!("could not read {} because of {}", self.path, err), kind: BotErrorKind::WorkshopError }
+        })?;
+        Ok(requested(parse_dump(&file)?, ids))
+    }
+}
+
+impl WorkshopFetcher for CommandFetcher {
+    fn fetch(&self, ids: &[String]) -> Result<Vec<WorkshopMetadata>, IvanError> {
+        let numbers: Vec<String> = ids.iter().map(|id| workshop_number(id)).collect();
+        let output = Command::new(&self.command).args(&numbers).output().map_err(|err| {
+            IvanError { input: format!("could not run {} because of {}", self.command, err), kind: BotErrorKind::WorkshopError }
+        })?;
+        if !output.status.success() {
+            return Err(IvanError { input: format!("{} exited with {}", self.command, output.status), kind: BotErrorKind::WorkshopError });
+        }
+        Ok(requested(parse_dump(&output.stdout)?, ids))
+    }
+}
+
+/// Only the metadata of the requested ids, a dump or command can return more.
+fn requested(metadata: Vec<WorkshopMetadata>, ids: &[String]) -> Vec<WorkshopMetadata> {
+    metadata.into_iter().filter(|metadata| {
+        ids.iter().any(|id| id.eq_ignore_ascii_case(&metadata.id))
+    }).collect()
+}
+
+pub fn get_fetcher() -> Option<Box<dyn WorkshopFetcher>> {
+    if let Ok(command) = var("WORKSHOP_FETCH_COMMAND") {
+        return Some(Box::new(CommandFetcher { command }));
+    }
+    var("WORKSHOP_DUMP").ok().map(|path| Box::new(DumpFetcher { path }) as Box<dyn WorkshopFetcher>)
+}
+
+/// Accepts either a Steam `GetPublishedFileDetails` response or a list of `WorkshopMetadata`.
+pub fn parse_dump(bytes: &[u8]) -> Result<Vec<WorkshopMetadata>, IvanError> {
+    let value: Value = serde_json::from_slice(bytes).map_err(|err| {
+        IvanError { input: format!("workshop dump is not valid json: {}", err), kind: BotErrorKind::WorkshopError }
+    })?;
+    if value.is_array() {
+        return serde_json::from_value(value).map_err(|err| {
+            IvanError { input: format!("invalid workshop metadata list: {}", err), kind: BotErrorKind::WorkshopError }
+        });
+    }
+    let details = value.pointer("/response/publishedfiledetails").and_then(|value| value.as_array()).ok_or_else(|| {
+        IvanError { input: "workshop dump has no response.publishedfiledetails".to_string(), kind: BotErrorKind::WorkshopError }
+    })?;
+    Ok(details.iter().filter_map(|detail| {
+        let id = json_string(detail.get("publishedfileid"))?;
+        let title = json_string(detail.get("title"))?;
+        Some(WorkshopMetadata {
+            id: format!("UGC{}", id),
+            title,
+            preview_url: json_string(detail.get("preview_url")),
+            file_size: json_string(detail.get("file_size")).and_then(|size| size.parse().ok()).unwrap_or(0),
+            modes: detail.get("tags").and_then(|tags| tags.as_array()).map(|tags| {
+                tags.iter().filter_map(|tag| json_string(tag.get("tag"))).collect()
+            }).unwrap_or_default(),
+        })
+    }).collect())
+}
+
+/// Steam returns numbers as strings in some places and as numbers in others.
+fn json_string(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(string) => Some(string.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None
+    }
+}
+
+fn workshop_number(id: &str) -> String {
+    id.trim_start_matches(|c: char| !c.is_ascii_digit()).to_string()
+}
+
+pub fn handle_workshop(arguments: &Vec<&str>, msg: &Message, config: &mut IvanConfig) -> Result<String, IvanError> {
+    match pa(arguments, 1, HELP_WORKSHOP_ARGUMENT)?.to_lowercase().as_str() {
+        "import" => {
+            let attachment = msg.attachments.first().ok_or_else(|| {
+                IvanError { input: "attach a workshop dump json file to the message".to_string(), kind: BotErrorKind::MissingArgument }
+            })?;
+            let bytes = attachment.download().map_err(|err| {
+                IvanError { input: err.to_string(), kind: BotErrorKind::DiscordError }
+            })?;
+            store_metadata(parse_dump(&bytes)?, config)
+        }
+        "fetch" => {
+            let fetcher = get_fetcher().ok_or_else(|| {
+                IvanError { input: "set WORKSHOP_DUMP or WORKSHOP_FETCH_COMMAND to fetch workshop metadata".to_string(), kind: BotErrorKind::WorkshopError }
+            })?;
+            let ids: Vec<String> = config.get_maps().iter().filter(|map| {
+                map.map.to_uppercase().starts_with("UGC")
+            }).map(|map| map.map.to_uppercase()).collect();
+            store_metadata(fetcher.fetch(&ids)?, config)
+        }
+        "list" => Ok(config.get_workshop_metadata().iter().fold("".to_string(), |a, metadata| {
+            format!("{}\n{}: \"{}\" {} KB modes: [{}]", a, metadata.id, metadata.title, metadata.file_size / 1024, metadata.modes.join(", "))
+        })),
+        x => invalid_argument(x, HELP_WORKSHOP_ARGUMENT)
+    }
+}
+
+fn store_metadata(metadata: Vec<WorkshopMetadata>, config: &mut IvanConfig) -> Result<String, IvanError> {
+    let catalog = config.get_catalog();
+    let metadata: Vec<WorkshopMetadata> = metadata.into_iter().map(|mut value| {
+        value.modes = value.modes.iter().filter_map(|mode| catalog.find_mode(mode).map(|entry| entry.name.clone())).collect();
+        value
+    }).collect();
+    let amount = metadata.len();
+    config.add_workshop_metadata(metadata)?;
+    Ok(format!("Stored workshop metadata for {} maps", amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn steam_responses_are_parsed() {
+        let dump = json!({"response": {"publishedfiledetails": [
+            {"publishedfileid": "2451245", "title": "Dust", "preview_url": "https://example.com/dust.jpg", "file_size": "2048", "tags": [{"tag": "SND"}, {"tag": "TDM"}]},
+            {"publishedfileid": 1664873782, "title": "Santorini"},
+            {"publishedfileid": "404"}
+        ]}});
+        let metadata = parse_dump(dump.to_string().as_bytes()).unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata[0].id, "UGC2451245");
+        assert_eq!(metadata[0].file_size, 2048);
+        assert_eq!(metadata[0].modes, vec!["SND".to_string(), "TDM".to_string()]);
+        assert_eq!(metadata[1].id, "UGC1664873782");
+        assert_eq!(metadata[1].preview_url, None);
+    }
+
+    #[test]
+    fn metadata_lists_are_parsed() {
+        let dump = json!([{"id": "UGC2451245", "title": "Dust"}]);
+        let metadata = parse_dump(dump.to_string().as_bytes()).unwrap();
+        assert_eq!(metadata[0].title, "Dust");
+        assert_eq!(metadata[0].file_size, 0);
+        assert!(parse_dump(b"{}").is_err());
+        assert!(parse_dump(b"not json").is_err());
+    }
+
+    #[test]
+    fn only_requested_ids_are_kept() {
+        let dump = json!([{"id": "UGC1", "title": "One"}, {"id": "UGC2", "title": "Two"}]);
+        let metadata = requested(parse_dump(dump.to_string().as_bytes()).unwrap(), &["ugc2".to_string()]);
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].id, "UGC2");
+    }
+}